@@ -22,23 +22,21 @@ pub(super) fn to_snake_case(in_name: &str) -> String {
     let mut chars = in_name.chars();
     let mut prev = '_';
     let mut curr = '_';
-    while let Some( next ) = chars.next() {
+    for next in chars.by_ref() {
         if next != '_' {
             curr = next;
             break;
         }
         // skipping leading underscores otheriwse
     }
-    while let Some( next ) = chars.next() {
+    for next in chars {
         if curr == '_' && prev == '_' {
             // skipping consecutive underscores
             continue;
         }
         if curr.is_ascii_uppercase() {
-            if prev != '_' {
-                if !prev.is_ascii_uppercase() || next != '_' && !next.is_ascii_uppercase() {
-                    out_name.push('_');
-                }
+            if prev != '_' && (!prev.is_ascii_uppercase() || next != '_' && !next.is_ascii_uppercase()) {
+                out_name.push('_');
             }
             out_name.push(curr.to_ascii_lowercase());
         } else {
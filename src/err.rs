@@ -9,7 +9,7 @@ pub(crate) enum Error {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &*self {
+        match self {
             Error::Sql(msg) => write!(f, "{}", msg),
             Error::IO(err) => err.fmt(f)
         }
@@ -1,60 +1,408 @@
 use proc_macro2::{TokenStream, Span, Group, Delimiter, Literal, Ident, Punct, Spacing};
 use quote::{ToTokens, TokenStreamExt};
-use crate::sql::{IncludedSql, Stmt, StmtItem, TakeStmtItem};
+use crate::sql::{choice_variant, is_option, IncludedSql, ParamMode, Stmt, StmtAttr, StmtItem, StmtParam, TakeStmtItem};
 use crate::conv::StringExt;
+use crate::err::{self, Result};
+use syn::parse::Parser;
 
-impl ToTokens for IncludedSql {
-    fn to_tokens(&self, tokens: &mut TokenStream) {
+/// Version of the `impl_sql` input protocol.
+///
+/// Newer protocol versions pass more information to `impl_sql`. They are opt-in
+/// (via the `protocol` option of `include_sql`) as `impl_sql` implementations that
+/// were written for an older protocol would not be able to parse the newer input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub(crate) enum Protocol {
+    /// The original protocol
+    #[default]
+    V1 = 1,
+    /// Adds the file doc-comment after the generated trait name
+    V2 = 2,
+    /// Keyword-tagged protocol with an explicit version token
+    V3 = 3,
+}
+
+impl Protocol {
+    /// Returns the protocol with the specified version number.
+    pub(crate) fn from_version(version: u32) -> Option<Self> {
+        match version {
+            1 => Some(Self::V1),
+            2 => Some(Self::V2),
+            3 => Some(Self::V3),
+            _ => None
+        }
+    }
+}
+
+/// Statement feature described by the requirement phrase of its error message
+/// and the predicate that checks whether the statement uses it
+type Feature = (&'static str, fn(&Stmt) -> bool);
+
+/// Statement features that can only be passed to `impl_sql` via protocol version 3
+const V3_FEATURES : [Feature; 14] = [
+    ("attributes require", |stmt| !stmt.attrs.is_empty()),
+    ("Rust attributes require", |stmt| !stmt.rust_attrs.is_empty()),
+    ("OUT and INOUT parameters require", |stmt| stmt.params.iter().any(|param| param.mode != ParamMode::In)),
+    ("parameter defaults require", |stmt| stmt.params.iter().any(|param| param.default.is_some())),
+    ("tuple list parameters require", |stmt| stmt.unique_binds().iter().any(|item| matches!(item, StmtItem::Tuples(..)))),
+    ("struct field placeholders require", |stmt| stmt.unique_binds().iter().any(|item| matches!(item, StmtItem::Field(..)))),
+    ("identifier parameters require", |stmt| stmt.unique_binds().iter().any(|item| matches!(item, StmtItem::Ident(..)))),
+    ("enum parameters require", |stmt| stmt.params.iter().any(|param| !param.values.is_empty())),
+    ("nullable parameter markers require", |stmt| !stmt.nullable.is_empty()),
+    ("result cardinality requires", |stmt| stmt.cardinality.is_some()),
+    ("result columns require", |stmt| !stmt.columns.is_empty()),
+    ("dialect variants require either the `dialect` option or", |stmt| stmt.dialect.is_some()),
+    ("conditional compilation requires", |stmt| stmt.cfg.is_some() || stmt.items.iter().any(|item| matches!(item, StmtItem::Cfg(..)))),
+    ("optional fragments require", |stmt| stmt.items.iter().any(|item| matches!(item, StmtItem::Opt(..)))),
+];
+
+impl IncludedSql {
+    /// Checks whether everything that was found in the SQL file can be passed
+    /// to `impl_sql` using the specified protocol version.
+    pub(crate) fn check_protocol(&self, protocol: Protocol) -> Result<()> {
+        if protocol < Protocol::V3 {
+            for stmt in &self.stmt_list {
+                if let Some((requirement, _)) = V3_FEATURES.iter().find(|(_, is_used)| is_used(stmt)) {
+                    return Err(err::new(format!("statement `{}` {} impl_sql protocol version 3", &stmt.name, requirement)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates enums of allowed identifiers of identifier parameters and
+    /// enums of values of enumerated parameters.
+    ///
+    /// Each enum is named after its parameter and is generated once even when
+    /// several statements declare the same parameter.
+    pub(crate) fn to_param_types_tokens(&self, tokens: &mut TokenStream) {
+        let mut type_names : Vec<&str> = Vec::new();
+        for param in self.stmt_list.iter().flat_map(|stmt| stmt.params.iter()) {
+            if !param.is_enum() || type_names.contains(&param.rust_type.as_str()) {
+                continue;
+            }
+            type_names.push(&param.rust_type);
+            let type_name = Ident::new(&param.rust_type, Span::call_site());
+            let choices = if param.values.is_empty() { &param.idents } else { &param.values };
+            let variants = choices.iter().map(|choice| Ident::new(&choice_variant(choice), Span::call_site())).collect::<Vec<_>>();
+            if param.values.is_empty() {
+                let docs = format!("Identifiers that `{}` can be substituted with", param.name);
+                tokens.extend(quote::quote! {
+                    #[doc = #docs]
+                    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                    pub enum #type_name {
+                        #( #variants ),*
+                    }
+
+                    impl #type_name {
+                        /// Returns the identifier as it is written in SQL.
+                        pub fn as_str(&self) -> &'static str {
+                            match self {
+                                #( Self::#variants => #choices ),*
+                            }
+                        }
+                    }
+                });
+            } else {
+                let docs = format!("Values of `{}`", param.name);
+                tokens.extend(quote::quote! {
+                    #[doc = #docs]
+                    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                    pub enum #type_name {
+                        #( #variants ),*
+                    }
+
+                    impl #type_name {
+                        /// Returns the value as it is stored in the database.
+                        pub fn as_str(&self) -> &'static str {
+                            match self {
+                                #( Self::#variants => #choices ),*
+                            }
+                        }
+                    }
+
+                    impl ::std::fmt::Display for #type_name {
+                        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                            f.write_str(self.as_str())
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    /// Generates `impl_sql` call using the specified protocol version.
+    pub(crate) fn to_impl_sql_tokens(&self, protocol: Protocol, tokens: &mut TokenStream) {
         tokens.append(Ident::new("impl_sql", Span::call_site()));
         tokens.append(Punct::new('!', Spacing::Alone));
 
         let mut macro_args = TokenStream::new();
-        let mut name = self.file_name.to_camel_case(); // crate::conv::to_camel_case(&self.file_name);
-        name.push_str("Sql");
-        macro_args.append(Ident::new(&name, Span::call_site()));
-        macro_args.append(Punct::new('=', Spacing::Alone));
+        let name = self.type_name();
+        let docs = self.docs.as_deref().unwrap_or_default();
+
+        if protocol >= Protocol::V3 {
+            append_key("version", &mut macro_args);
+            macro_args.append(Literal::u32_unsuffixed(protocol as u32));
+            macro_args.append(Punct::new(',', Spacing::Alone));
 
-        macro_args.append_separated(&self.stmt_list, Punct::new(',', Spacing::Alone));
+            append_key("name", &mut macro_args);
+            macro_args.append(Ident::new(&name, Span::call_site()));
+            macro_args.append(Punct::new(',', Spacing::Alone));
+
+            append_key("docs", &mut macro_args);
+            macro_args.append(Literal::string(docs));
+            macro_args.append(Punct::new(',', Spacing::Alone));
+
+            append_key("stmts", &mut macro_args);
+            let mut stmts = TokenStream::new();
+            for (i, stmt) in self.stmt_list.iter().enumerate() {
+                if i > 0 {
+                    stmts.append(Punct::new(',', Spacing::Alone));
+                }
+                stmt.to_keyword_tokens(&mut stmts);
+            }
+            macro_args.append(Group::new(Delimiter::Bracket, stmts));
+        } else {
+            macro_args.append(Ident::new(&name, Span::call_site()));
+            if protocol >= Protocol::V2 {
+                macro_args.append(Literal::string(docs));
+            }
+            macro_args.append(Punct::new('=', Spacing::Alone));
+
+            macro_args.append_separated(&self.stmt_list, Punct::new(',', Spacing::Alone));
+        }
 
         tokens.append(Group::new(Delimiter::Brace, macro_args));
     }
 }
 
+/// Appends `key =` to the keyword-tagged protocol arguments.
+fn append_key(key: &str, tokens: &mut TokenStream) {
+    tokens.append(Ident::new(key, Span::call_site()));
+    tokens.append(Punct::new('=', Spacing::Alone));
+}
+
 impl ToTokens for Stmt {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let mut stmt_tokens = TokenStream::new();
 
+        self.to_into_tokens(&mut stmt_tokens);
+        stmt_tokens.append(Ident::new(&self.name, Span::call_site()));
+        stmt_tokens.append(Group::new(Delimiter::Parenthesis, self.params_tokens()));
+        stmt_tokens.append(Literal::string(self.docs.as_deref().unwrap_or_default()));
+        stmt_tokens.append(Punct::new('$', Spacing::Alone));
+        stmt_tokens.append_all(self.legacy_items());
+
+        tokens.append(Group::new(Delimiter::Brace, stmt_tokens));
+    }
+}
+
+impl Stmt {
+    /// Generates statement arguments of the keyword-tagged protocol.
+    fn to_keyword_tokens(&self, tokens: &mut TokenStream) {
+        let mut stmt_tokens = TokenStream::new();
+
+        append_key("name", &mut stmt_tokens);
+        stmt_tokens.append(Ident::new(&self.name, Span::call_site()));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("kind", &mut stmt_tokens);
+        self.to_into_tokens(&mut stmt_tokens);
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("params", &mut stmt_tokens);
+        stmt_tokens.append(Group::new(Delimiter::Bracket, self.params_tokens()));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("docs", &mut stmt_tokens);
+        stmt_tokens.append(Literal::string(self.docs.as_deref().unwrap_or_default()));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("text", &mut stmt_tokens);
+        let mut text_tokens = TokenStream::new();
+        text_tokens.append_all(&self.items);
+        stmt_tokens.append(Group::new(Delimiter::Bracket, text_tokens));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("attrs", &mut stmt_tokens);
+        let mut attrs_tokens = TokenStream::new();
+        attrs_tokens.append_separated(&self.attrs, Punct::new(',', Spacing::Alone));
+        stmt_tokens.append(Group::new(Delimiter::Brace, attrs_tokens));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("class", &mut stmt_tokens);
+        stmt_tokens.append(Ident::new(self.class.as_str(), Span::call_site()));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("read_only", &mut stmt_tokens);
+        stmt_tokens.append(Ident::new(if self.read_only { "true" } else { "false" }, Span::call_site()));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("reads", &mut stmt_tokens);
+        stmt_tokens.append(Group::new(Delimiter::Bracket, names_tokens(&self.reads)));
+        stmt_tokens.append(Punct::new(',', Spacing::Alone));
+
+        append_key("writes", &mut stmt_tokens);
+        stmt_tokens.append(Group::new(Delimiter::Bracket, names_tokens(&self.writes)));
+
+        if self.params.iter().any(|param| param.default.is_some()) {
+            stmt_tokens.append(Punct::new(',', Spacing::Alone));
+            append_key("defaults", &mut stmt_tokens);
+            stmt_tokens.append(Group::new(Delimiter::Brace, self.defaults_tokens()));
+        }
+
+        if let Some(cardinality) = self.cardinality {
+            stmt_tokens.append(Punct::new(',', Spacing::Alone));
+            append_key("cardinality", &mut stmt_tokens);
+            stmt_tokens.append(Ident::new(cardinality.as_str(), Span::call_site()));
+        }
+
+        if !self.columns.is_empty() {
+            stmt_tokens.append(Punct::new(',', Spacing::Alone));
+            append_key("columns", &mut stmt_tokens);
+            stmt_tokens.append(Group::new(Delimiter::Bracket, self.columns_tokens()));
+        }
+
+        if !self.rust_attrs.is_empty() {
+            stmt_tokens.append(Punct::new(',', Spacing::Alone));
+            append_key("rust_attrs", &mut stmt_tokens);
+            let mut rust_attrs_tokens = TokenStream::new();
+            for rust_attr in &self.rust_attrs {
+                if let Ok(attrs) = syn::Attribute::parse_outer.parse_str(rust_attr) {
+                    rust_attrs_tokens.append_all(attrs);
+                }
+            }
+            stmt_tokens.append(Group::new(Delimiter::Bracket, rust_attrs_tokens));
+        }
+
+        if let Some(pred) = self.cfg.as_ref() {
+            stmt_tokens.append(Punct::new(',', Spacing::Alone));
+            append_key("cfg", &mut stmt_tokens);
+            stmt_tokens.append(Group::new(Delimiter::Parenthesis, cfg_tokens(pred)));
+        }
+
+        if let Some(dialect) = self.dialect.as_ref() {
+            stmt_tokens.append(Punct::new(',', Spacing::Alone));
+            append_key("dialect", &mut stmt_tokens);
+            stmt_tokens.append(Ident::new(dialect, Span::call_site()));
+        }
+
+        tokens.append(Group::new(Delimiter::Brace, stmt_tokens));
+    }
+
+    /// Returns statement items where null-safe comparisons are replaced by plain ones
+    /// as older protocols cannot pass them to `impl_sql`.
+    fn legacy_items(&self) -> Vec<StmtItem> {
+        let mut items : Vec<StmtItem> = Vec::with_capacity(self.items.len());
+        for item in &self.items {
+            if let StmtItem::NullSafe(name, op) = item {
+                match items.last_mut() {
+                    Some(StmtItem::Text(text)) => text.push_str(op),
+                    _ => items.push(StmtItem::Text(op.clone())),
+                }
+                items.push(StmtItem::Bind(name.clone()));
+            } else {
+                items.push(item.clone());
+            }
+        }
+        items
+    }
+
+    /// Generates statement variant selector.
+    fn to_into_tokens(&self, tokens: &mut TokenStream) {
         let last = self.into.len() - 1;
         for (i, ch) in self.into.chars().enumerate() {
             let spacing = if i < last { Spacing::Joint } else { Spacing::Alone };
-            stmt_tokens.append(Punct::new(ch, spacing));
+            tokens.append(Punct::new(ch, spacing));
         }
+    }
 
-        stmt_tokens.append(Ident::new(&self.name, Span::call_site()));
+    /// Generates the set of parameter defaults as `name = (expr)` pairs.
+    fn defaults_tokens(&self) -> TokenStream {
+        let mut defaults_tokens = TokenStream::new();
+        for param in &self.params {
+            let Some(default) = param.default.as_ref() else { continue; };
+            let Ok(expr) = syn::parse_str::<syn::Expr>(default) else { continue; };
+            if !defaults_tokens.is_empty() {
+                defaults_tokens.append(Punct::new(',', Spacing::Alone));
+            }
+            append_key(&param.name, &mut defaults_tokens);
+            defaults_tokens.append(Group::new(Delimiter::Parenthesis, expr.into_token_stream()));
+        }
+        defaults_tokens
+    }
+
+    /// Generates the list of result columns as `name: Type` pairs.
+    fn columns_tokens(&self) -> TokenStream {
+        let mut columns_tokens = TokenStream::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                columns_tokens.append(Punct::new(',', Spacing::Alone));
+            }
+            columns_tokens.append(Ident::new(&column.name, Span::call_site()));
+            columns_tokens.append(Punct::new(':', Spacing::Alone));
+            if let Ok(column_type) = syn::parse_str::<syn::Type>(&column.rust_type) {
+                column_type.to_tokens(&mut columns_tokens);
+            }
+        }
+        columns_tokens
+    }
 
+    /// Generates the list of statement parameters.
+    fn params_tokens(&self) -> TokenStream {
         let mut stmt_params = TokenStream::new();
         let mut binds = self.unique_binds();
-        for param in &self.params {
-            if let Some(bind) = binds.take_by_name(&param.name) {
-                bind.to_params_tokens(Some(param.rust_type.as_str()), &mut stmt_params);
+        // parameters listed by `params:` go first, then declared ones
+        let names = self.order.iter().chain(self.params.iter().map(|param| &param.name));
+        for name in names {
+            if let Some(bind) = binds.take_by_name(name) {
+                let param = self.params.iter().find(|param| &param.name == name);
+                let is_optional = self.is_optional(name) || self.is_nullable(name);
+                bind.to_params_tokens(param, is_optional, &mut stmt_params);
             }
         }
         // append remaining untyped parameter placeholders
         for bind in binds {
-            bind.to_params_tokens(None, &mut stmt_params);
+            let is_optional = matches!(bind, StmtItem::Bind(name) | StmtItem::NullSafe(name, _) if self.is_optional(name) || self.is_nullable(name));
+            bind.to_params_tokens(None, is_optional, &mut stmt_params);
         }
+        stmt_params
+    }
+}
 
-        stmt_tokens.append(Group::new(Delimiter::Parenthesis, stmt_params));
-
-        if let Some(doc_comment) = self.docs.as_ref() {
-            stmt_tokens.append(Literal::string(doc_comment));
+impl ToTokens for StmtAttr {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        append_key(&self.name, tokens);
+        if self.value.is_empty() {
+            // attribute without a value is a flag
+            tokens.append(Ident::new("true", Span::call_site()));
+        } else if let Some(lit) = syn::parse_str::<syn::Lit>(&self.value).ok().filter(is_plain_value) {
+            lit.to_tokens(tokens);
         } else {
-            stmt_tokens.append(Literal::string(""));
+            tokens.append(Literal::string(&self.value));
         }
-        stmt_tokens.append(Punct::new('$', Spacing::Alone));
-        stmt_tokens.append_all(&self.items);
+    }
+}
 
-        tokens.append(Group::new(Delimiter::Brace, stmt_tokens));
+/// Generates a comma separated list of string literals.
+fn names_tokens(names: &[String]) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    tokens.append_separated(names.iter().map(|name| Literal::string(name)), Punct::new(',', Spacing::Alone));
+    tokens
+}
+
+/// Converts `cfg` predicate into tokens.
+fn cfg_tokens(pred: &str) -> TokenStream {
+    syn::parse_str::<syn::Meta>(pred).map(|meta| meta.into_token_stream()).unwrap_or_default()
+}
+
+/// Checks whether the literal is a string, a boolean, or a number without a suffix.
+/// Anything else (like `30s`) is passed to `impl_sql` as a string.
+fn is_plain_value(lit: &syn::Lit) -> bool {
+    match lit {
+        syn::Lit::Str(_) | syn::Lit::Bool(_) => true,
+        syn::Lit::Int(int) => int.suffix().is_empty(),
+        syn::Lit::Float(float) => float.suffix().is_empty(),
+        _ => false
     }
 }
 
@@ -71,37 +419,151 @@ impl ToTokens for StmtItem {
             Self::List( name ) => {
                 tokens.append(Punct::new('#', Spacing::Alone));
                 tokens.append(Ident::new(name, Span::call_site()));
+            },
+            Self::Tuples( name, fields ) => {
+                tokens.append(Punct::new('*', Spacing::Alone));
+                tokens.append(Ident::new(name, Span::call_site()));
+                let mut field_tokens = TokenStream::new();
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        field_tokens.append(Punct::new(',', Spacing::Alone));
+                    }
+                    match field.parse::<usize>() {
+                        Ok(index) => field_tokens.append(Literal::usize_unsuffixed(index)),
+                        Err(_) => field_tokens.append(Ident::new(field, Span::call_site())),
+                    }
+                }
+                tokens.append(Group::new(Delimiter::Bracket, field_tokens));
+            },
+            Self::Field( name, path ) => {
+                tokens.append(Punct::new('.', Spacing::Alone));
+                tokens.append(Ident::new(name, Span::call_site()));
+                let mut path_tokens = TokenStream::new();
+                for (i, field) in path.iter().enumerate() {
+                    if i > 0 {
+                        path_tokens.append(Punct::new('.', Spacing::Alone));
+                    }
+                    path_tokens.append(Ident::new(field, Span::call_site()));
+                }
+                tokens.append(Group::new(Delimiter::Bracket, path_tokens));
+            },
+            Self::Ident( name ) => {
+                tokens.append(Punct::new('@', Spacing::Alone));
+                tokens.append(Ident::new(name, Span::call_site()));
+            },
+            Self::NullSafe( name, op ) => {
+                // `==` for null-safe equality, `!=` for null-safe inequality
+                let first = if op.trim_end() == "=" { '=' } else { '!' };
+                tokens.append(Punct::new(first, Spacing::Joint));
+                tokens.append(Punct::new('=', Spacing::Alone));
+                tokens.append(Ident::new(name, Span::call_site()));
+            },
+            Self::Cfg( pred, items ) => {
+                tokens.append(Punct::new('#', Spacing::Alone));
+                let mut attr_tokens = TokenStream::new();
+                attr_tokens.append(Ident::new("cfg", Span::call_site()));
+                attr_tokens.append(Group::new(Delimiter::Parenthesis, cfg_tokens(pred)));
+                tokens.append(Group::new(Delimiter::Bracket, attr_tokens));
+                let mut group_tokens = TokenStream::new();
+                group_tokens.append_all(items);
+                tokens.append(Group::new(Delimiter::Bracket, group_tokens));
+            },
+            Self::Opt( name, items ) => {
+                tokens.append(Punct::new('?', Spacing::Alone));
+                tokens.append(Ident::new(name, Span::call_site()));
+                let mut group_tokens = TokenStream::new();
+                group_tokens.append_all(items);
+                tokens.append(Group::new(Delimiter::Bracket, group_tokens));
             }
         }
     }
 }
 
 impl StmtItem {
-    fn to_params_tokens(&self, opt_type_name: Option<&str>, stmt_params: &mut TokenStream) {
+    /// Generates parameter description.
+    ///
+    /// Scalar parameters that are used only in optional fragments, or that are marked as nullable,
+    /// are passed as `Option`s.
+    /// Scalar parameters are tagged by their mode - `:` for IN, `>` for OUT, and `<>` for INOUT.
+    /// IN lists are tagged by `#` and tuple lists by `*`. Both are described by their element type.
+    /// Struct parameters, whose fields are bound by field placeholders, are tagged by `.`.
+    /// Identifier parameters are tagged by `@` and typed by the enum of their allowed identifiers.
+    /// Enumerated parameters are tagged by `%` and typed by the enum of their values.
+    fn to_params_tokens(&self, param: Option<&StmtParam>, is_optional: bool, stmt_params: &mut TokenStream) {
+        let opt_type_name = param.map(|param| param.rust_type.as_str());
+        let mode = param.map(|param| param.mode).unwrap_or_default();
         match self {
-            StmtItem::Bind(name) => {
-                stmt_params.append(Punct::new(':', Spacing::Alone));
+            StmtItem::Bind(name) | StmtItem::NullSafe(name, _) => {
+                match mode {
+                    ParamMode::In if param.is_some_and(StmtParam::is_enum) => stmt_params.append(Punct::new('%', Spacing::Alone)),
+                    ParamMode::In => stmt_params.append(Punct::new(':', Spacing::Alone)),
+                    ParamMode::Out => stmt_params.append(Punct::new('>', Spacing::Alone)),
+                    ParamMode::InOut => {
+                        stmt_params.append(Punct::new('<', Spacing::Joint));
+                        stmt_params.append(Punct::new('>', Spacing::Alone));
+                    }
+                }
                 stmt_params.append(Ident::new(name, Span::call_site()));
                 let type_tree = opt_type_name
                     .and_then(|type_name| syn::parse_str::<syn::Type>(type_name).ok())
                     .map(|param_type| {
                         let mut type_tokens = TokenStream::new();
                         #[cfg(feature = "async")]
-                        if let syn::Type::Reference(_) = &param_type {
-                            lifetime(name).to_tokens(&mut type_tokens);
+                        let param_type = if is_optional || is_option(&param_type) {
+                            // references in `Option` cannot be preceded by the lifetime, thus it is set on the reference itself
+                            with_ref_lifetime(name, param_type)
+                        } else {
+                            if let syn::Type::Reference(_) = &param_type {
+                                lifetime(name).to_tokens(&mut type_tokens);
+                            }
+                            param_type
+                        };
+                        if is_optional && !is_option(&param_type) {
+                            append_option(param_type.into_token_stream(), &mut type_tokens);
+                        } else {
+                            param_type.to_tokens(&mut type_tokens);
                         }
-                        param_type.to_tokens(&mut type_tokens);
                         Group::new(Delimiter::Parenthesis, type_tokens)
                     })
                 ;
                 if let Some(tt) = type_tree {
                     stmt_params.append(tt);
+                } else if is_optional {
+                    let mut type_tokens = TokenStream::new();
+                    append_option(Ident::new("_", Span::call_site()).into_token_stream(), &mut type_tokens);
+                    stmt_params.append(Group::new(Delimiter::Parenthesis, type_tokens));
                 } else {
                     stmt_params.append(Ident::new("_", Span::call_site()));
                 }
             },
-            StmtItem::List(name) => {
-                stmt_params.append(Punct::new('#', Spacing::Alone));
+            StmtItem::Field(name, _) => {
+                stmt_params.append(Punct::new('.', Spacing::Alone));
+                stmt_params.append(Ident::new(name, Span::call_site()));
+                let type_tree = opt_type_name
+                    .and_then(|type_name| syn::parse_str::<syn::Type>(type_name).ok())
+                    .map(|param_type| {
+                        let mut type_tokens = TokenStream::new();
+                        #[cfg(feature = "async")]
+                        if let syn::Type::Reference(_) = &param_type {
+                            lifetime(name).to_tokens(&mut type_tokens);
+                        }
+                        param_type.to_tokens(&mut type_tokens);
+                        Group::new(Delimiter::Parenthesis, type_tokens)
+                    })
+                ;
+                stmt_params.extend(type_tree);
+            },
+            StmtItem::Ident(name) => {
+                stmt_params.append(Punct::new('@', Spacing::Alone));
+                stmt_params.append(Ident::new(name, Span::call_site()));
+                if let Some(type_name) = opt_type_name {
+                    let type_ident = Ident::new(type_name, Span::call_site());
+                    stmt_params.append(Group::new(Delimiter::Parenthesis, type_ident.into_token_stream()));
+                }
+            },
+            StmtItem::List(name) | StmtItem::Tuples(name, _) => {
+                let tag = if matches!(self, StmtItem::Tuples(..)) { '*' } else { '#' };
+                stmt_params.append(Punct::new(tag, Spacing::Alone));
                 stmt_params.append(Ident::new(name, Span::call_site()));
                 let type_tree = opt_type_name
                     .and_then(|type_name| syn::parse_str::<syn::Type>(type_name).ok())
@@ -136,7 +598,556 @@ impl StmtItem {
     }
 }
 
+/// Appends `Option<T>`.
+fn append_option(param_type: TokenStream, tokens: &mut TokenStream) {
+    tokens.append(Ident::new("Option", Span::call_site()));
+    tokens.append(Punct::new('<', Spacing::Alone));
+    tokens.extend(param_type);
+    tokens.append(Punct::new('>', Spacing::Alone));
+}
+
 #[cfg(feature = "async")]
 fn lifetime(name: &str) -> syn::Lifetime {
     syn::Lifetime { apostrophe: Span::call_site(), ident: Ident::new(name, Span::call_site()) }
-}
\ No newline at end of file
+}
+
+/// Sets the parameter lifetime on the reference that is either the type itself or the argument of its `Option`.
+/// References that already have a lifetime are left as they are.
+#[cfg(feature = "async")]
+fn with_ref_lifetime(name: &str, mut param_type: syn::Type) -> syn::Type {
+    let type_ref = match &mut param_type {
+        syn::Type::Reference(type_ref) => Some(type_ref),
+        syn::Type::Path(type_path) => match type_path.path.segments.last_mut().map(|segment| &mut segment.arguments) {
+            Some(syn::PathArguments::AngleBracketed(args)) => match args.args.first_mut() {
+                Some(syn::GenericArgument::Type(syn::Type::Reference(type_ref))) => Some(type_ref),
+                _ => None
+            },
+            _ => None
+        },
+        _ => None
+    };
+    if let Some(type_ref) = type_ref.filter(|type_ref| type_ref.lifetime.is_none()) {
+        type_ref.lifetime = Some(lifetime(name));
+    }
+    param_type
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Protocol;
+    use proc_macro2::TokenStream;
+
+    fn impl_sql_call(text: &str, protocol: Protocol) -> String {
+        let sql = crate::sql::parse(text, "library").unwrap();
+        let mut tokens = TokenStream::new();
+        sql.to_impl_sql_tokens(protocol, &mut tokens);
+        tokens.to_string()
+    }
+
+    #[test]
+    fn protocol_versions() {
+        assert_eq!(Protocol::default(), Protocol::V1);
+        assert_eq!(Protocol::from_version(1), Some(Protocol::V1));
+        assert_eq!(Protocol::from_version(2), Some(Protocol::V2));
+        assert_eq!(Protocol::from_version(3), Some(Protocol::V3));
+        assert_eq!(Protocol::from_version(0), None);
+        assert_eq!(Protocol::from_version(4), None);
+    }
+
+    #[test]
+    fn protocol_v3_features() {
+        let cases = [
+            ("-- name: get_books?\n-- @timeout: 30s\nSELECT * FROM books", "attributes require"),
+            ("-- name: get_books?\n-- #[must_use]\nSELECT * FROM books", "Rust attributes require"),
+            ("-- name: get_books!\n-- param: total: out i64\nCALL count_books(:total)", "OUT and INOUT parameters require"),
+            ("-- name: get_books?\n-- param: limit: i64 = 100\nSELECT * FROM books LIMIT :limit", "parameter defaults require"),
+            ("-- name: get_books!\nINSERT INTO books (title, author_id) VALUES :rows(title, author_id)", "tuple list parameters require"),
+            ("-- name: get_books!\n-- param: book: &NewBook\nINSERT INTO books (title) VALUES (:book.title)", "struct field placeholders require"),
+            ("-- name: get_books?\n-- param: sort_col: ident(title)\nSELECT * FROM books ORDER BY :!sort_col", "identifier parameters require"),
+            ("-- name: get_books?\n-- param: status: enum(active, archived)\nSELECT * FROM books WHERE status = :status", "enum parameters require"),
+            ("-- name: get_books?\nSELECT * FROM books WHERE editor = :editor?", "nullable parameter markers require"),
+            ("-- name: get_books?1\nSELECT * FROM books WHERE id = :id", "result cardinality requires"),
+            ("-- name: get_books?\n-- returns: title: String\nSELECT title FROM books", "result columns require"),
+            ("-- name: get_books? [postgres]\nSELECT * FROM books\n/\n-- name: get_books?\nSELECT * FROM books", "dialect variants require either the `dialect` option or"),
+            ("-- name: get_books?\n-- cfg: feature = \"postgres\"\nSELECT * FROM books", "conditional compilation requires"),
+            ("-- name: get_books?\nSELECT * FROM books WHERE 1 = 1\n--? if :status\n AND status = :status\n--? end", "optional fragments require"),
+        ];
+        for (text, requirement) in cases {
+            let mut sql = crate::sql::parse(text, "library").unwrap();
+            sql.select_dialect(None).unwrap();
+            for protocol in [Protocol::V1, Protocol::V2] {
+                let res = sql.check_protocol(protocol);
+                assert!(res.is_err(), "{}", text);
+                assert_eq!(res.unwrap_err().to_string(), format!("statement `get_books` {} impl_sql protocol version 3", requirement));
+            }
+            assert!(sql.check_protocol(Protocol::V3).is_ok(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn protocol_v1() {
+        let text = "
+-- Library queries
+-- name: get_loaned_books?
+-- param: user_id: &str - user ID
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let call = impl_sql_call(text, Protocol::V1);
+        assert!(call.starts_with("impl_sql ! { LibrarySql = { ? get_loaned_books"), "{}", call);
+    }
+
+    #[test]
+    fn protocol_v2_file_docs() {
+        let text = "
+-- Library queries
+-- name: get_loaned_books?
+-- param: user_id: &str - user ID
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let call = impl_sql_call(text, Protocol::V2);
+        assert!(call.starts_with("impl_sql ! { LibrarySql \" Library queries\" = { ? get_loaned_books"), "{}", call);
+
+        let text = "
+-- name: get_loaned_books?
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let call = impl_sql_call(text, Protocol::V2);
+        assert!(call.starts_with("impl_sql ! { LibrarySql \"\" = {"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn protocol_v3() {
+        let text = "
+-- Library queries
+-- name: get_loaned_books?
+-- Returns the list of books loaned to a patron
+-- param: user_id: &str - user ID
+SELECT book_title FROM library WHERE loaned_to = :user_id ORDER BY 1
+/
+-- name: loan_books!
+UPDATE library SET loaned_to = :user_id WHERE book_id IN ( :book_ids )
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert_eq!(call, concat!(
+            "impl_sql ! { version = 3 , name = LibrarySql , docs = \" Library queries\" , stmts = [",
+                "{ name = get_loaned_books , kind = ? , params = [: user_id (& str)] , ",
+                "docs = \" Returns the list of books loaned to a patron\\n * `user_id` - user ID\" , ",
+                "text = [\"SELECT book_title FROM library WHERE loaned_to = \" : user_id \" ORDER BY 1\"] , attrs = { } , class = select , read_only = true , reads = [\"library\"] , writes = [] } , ",
+                "{ name = loan_books , kind = ! , params = [: user_id _ # book_ids [BookIds]] , docs = \"\" , ",
+                "text = [\"UPDATE library SET loaned_to = \" : user_id \" WHERE book_id IN ( \" # book_ids \" )\"] , attrs = { } , class = update , read_only = false , reads = [] , writes = [\"library\"] }",
+            "] }"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn protocol_v3_async() {
+        let text = "
+-- name: get_loaned_books?
+-- param: user_id: &str - user ID
+SELECT book_title FROM library WHERE loaned_to = :user_id
+/
+-- name: loan_books!
+UPDATE library SET loaned_to = :user_id WHERE book_id IN ( :book_ids )
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("{ name = get_loaned_books , kind = ? , params = [: user_id ('user_id & str)] ,"), "{}", call);
+        assert!(call.contains("{ name = loan_books , kind = ! , params = [: user_id _ # book_ids ['book_ids BookIds]] ,"), "{}", call);
+    }
+
+    #[test]
+    fn stmt_attrs() {
+        let text = "
+-- name: get_loaned_books?
+-- @timeout: 30s
+-- @cache_ttl: 60
+-- @replica: true
+-- attr: retry = \"twice\"
+-- attr: prepared
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("attrs = { timeout = \"30s\" , cache_ttl = 60 , replica = true , retry = \"twice\" , prepared = true }"), "{}", call);
+    }
+
+    #[test]
+    fn rust_attrs() {
+        let text = "
+-- name: get_loaned_books?
+-- #[must_use]
+-- #[deprecated(note = \"use get_loans\")]
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("writes = [] , rust_attrs = [# [must_use] # [deprecated (note = \"use get_loans\")]] }"), "{}", call);
+    }
+
+    #[test]
+    fn conditional_compilation() {
+        let text = "
+-- name: get_loaned_books?
+-- cfg: feature = \"postgres\"
+SELECT book_title
+  FROM library
+ WHERE loaned_to = :user_id
+-- #if feature = \"audit\"
+   AND audited
+-- #endif
+ ORDER BY 1
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains(concat!(
+            "text = [\"SELECT book_title\\n  FROM library\\n WHERE loaned_to = \" : user_id ",
+            "# [cfg (feature = \"audit\")] [\"\\n   AND audited\"] \"\\n ORDER BY 1\"] , attrs = { } , class = select , read_only = true , reads = [\"library\"] , writes = [] , ",
+            "cfg = (feature = \"postgres\") }"
+        )), "{}", call);
+    }
+
+    #[test]
+    fn dialect_variants() {
+        let text = "
+-- name: upsert_user! [postgres]
+INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO UPDATE SET name = excluded.name
+/
+-- name: upsert_user!
+MERGE INTO users USING (SELECT :id AS id, :name AS name) src ON users.id = src.id
+WHEN MATCHED THEN UPDATE SET name = src.name WHEN NOT MATCHED THEN INSERT (id, name) VALUES (src.id, src.name)
+        ";
+        let mut sql = crate::sql::parse(text, "library").unwrap();
+        sql.select_dialect(None).unwrap();
+        let mut tokens = TokenStream::new();
+        sql.to_impl_sql_tokens(Protocol::V3, &mut tokens);
+        let call = tokens.to_string();
+        assert!(call.contains("cfg = (feature = \"postgres\") , dialect = postgres }"), "{}", call);
+        assert!(call.contains("cfg = (not (any (feature = \"postgres\"))) }"), "{}", call);
+
+        let mut sql = crate::sql::parse(text, "library").unwrap();
+        sql.select_dialect(Some("postgres")).unwrap();
+        assert!(sql.check_protocol(Protocol::V1).is_ok());
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn optional_fragments() {
+        let text = "
+-- name: find_books?
+-- param: title: &str - book title
+-- param: status: &str - loan status
+-- param: year: Option<u16> - publication year
+SELECT book_title
+  FROM library
+ WHERE book_title LIKE :title
+--? if :status
+   AND status = :status
+--? end
+--? if :year
+   AND published = :year
+--? end
+--? if :author
+   AND author = :author
+--? end
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains(
+            "params = [: title (& str) : status (Option < & str >) : year (Option < u16 >) : author (Option < _ >)]"
+        ), "{}", call);
+        assert!(call.contains(
+            "? status [\"\\n   AND status = \" : status] ? year [\"\\n   AND published = \" : year]"
+        ), "{}", call);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn optional_fragments_async() {
+        let text = "
+-- name: find_books?
+-- param: title: &str - book title
+-- param: status: &str - loan status
+-- param: year: Option<u16> - publication year
+SELECT book_title
+  FROM library
+ WHERE book_title LIKE :title
+--? if :status
+   AND status = :status
+--? end
+--? if :year
+   AND published = :year
+--? end
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains(
+            "params = [: title ('title & str) : status (Option < & 'status str >) : year (Option < u16 >)]"
+        ), "{}", call);
+    }
+
+    #[test]
+    fn result_columns() {
+        let text = "
+-- name: get_loaned_books?
+-- returns: book_id: i64, title: String
+-- column: loaned_on: Option<DateTime<Utc>>
+SELECT book_id, title, loaned_on FROM library WHERE loaned_to = :user_id
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains(
+            "writes = [] , columns = [book_id : i64 , title : String , loaned_on : Option < DateTime < Utc > >] }"
+        ), "{}", call);
+    }
+
+    #[test]
+    fn result_cardinality() {
+        let text = "
+-- name: get_user?1
+SELECT * FROM users WHERE id = :id
+/
+-- name: delete_users :execrows
+DELETE FROM users WHERE status = :status
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("kind = ? ,"), "{}", call);
+        assert!(call.contains("writes = [] , cardinality = one }"), "{}", call);
+        assert!(call.contains("kind = ! ,"), "{}", call);
+        assert!(call.contains("writes = [\"users\"] , cardinality = rows_affected }"), "{}", call);
+    }
+
+    #[test]
+    fn referenced_tables() {
+        let text = "
+-- name: get_loans?
+SELECT b.title FROM books b JOIN loans l ON l.book_id = b.id
+/
+-- name: archive_loans!
+-- tables: reads = loans; writes = archive.loans, loans
+WITH moved AS (DELETE FROM loans RETURNING *) INSERT INTO archive.loans SELECT * FROM moved
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("reads = [\"books\" , \"loans\"] , writes = [] }"), "{}", call);
+        assert!(call.contains("reads = [\"loans\"] , writes = [\"archive.loans\" , \"loans\"] }"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn param_modes() {
+        let text = "
+-- name: transfer!
+-- param: from: i64 - source account
+-- param: new_balance: out f64 - resulting balance
+-- param: fee: inout f64 - transfer fee
+CALL transfer(:from, :to, :new_balance, :fee)
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [: from (i64) > new_balance (f64) <> fee (f64) : to _]"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn param_modes_async() {
+        let text = "
+-- name: transfer!
+-- param: from: &str - source account
+-- param: new_balance: out f64 - resulting balance
+CALL transfer(:from, :new_balance)
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [: from ('from & str) > new_balance (f64)]"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn tuple_lists() {
+        let text = "
+-- name: add_books!
+-- param: rows: (&str, i32) - books to add
+INSERT INTO books (title, author_id) VALUES :rows(title, author_id)
+
+-- name: get_loans?
+SELECT * FROM loans WHERE (book_id, borrower) IN (:pairs) AND returned = :returned
+
+-- name: add_authors!
+INSERT INTO authors (first_name, last_name) VALUES :authors(first_name, last_name)
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [* rows ((& str , i32))]"), "{}", call);
+        assert!(call.contains("text = [\"INSERT INTO books (title, author_id) VALUES \" * rows [0 , 1]]"), "{}", call);
+        assert!(call.contains("params = [# pairs [Pairs] : returned _]"), "{}", call);
+        assert!(call.contains("params = [* authors [Authors]]"), "{}", call);
+        assert!(call.contains("* authors [first_name , last_name]"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn tuple_lists_async() {
+        let text = "
+-- name: add_books!
+-- param: rows: (&str, i32) - books to add
+INSERT INTO books (title, author_id) VALUES :rows(title, author_id)
+
+-- name: get_loans?
+-- param: borrowers: &str
+SELECT * FROM loans WHERE borrower IN (:borrowers)
+
+-- name: add_authors!
+INSERT INTO authors (first_name, last_name) VALUES :authors(first_name, last_name)
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [* rows ('rows (& str , i32))]"), "{}", call);
+        assert!(call.contains("params = [# borrowers ('borrowers 'borrowers_item & str)]"), "{}", call);
+        assert!(call.contains("params = [* authors ['authors Authors]]"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn struct_fields() {
+        let text = "
+-- name: add_user!
+-- param: user: &NewUser - user to add
+INSERT INTO users (name, email, city, added_by) VALUES (:user.name, :user.email, :user.address.city, :added_by)
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [. user (& NewUser) : added_by _]"), "{}", call);
+        assert!(call.contains("text = [\"INSERT INTO users (name, email, city, added_by) VALUES (\" . user [name] \", \" . user [email] \", \" . user [address . city] \", \" : added_by \")\"]"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn struct_fields_async() {
+        let text = "
+-- name: add_user!
+-- param: user: &NewUser - user to add
+INSERT INTO users (name, email, added_by) VALUES (:user.name, :user.email, :added_by)
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [. user ('user & NewUser) : added_by _]"), "{}", call);
+        assert!(call.contains("text = [\"INSERT INTO users (name, email, added_by) VALUES (\" . user [name] \", \" . user [email] \", \" : added_by \")\"]"), "{}", call);
+    }
+
+    #[test]
+    fn ident_params() {
+        let text = "
+-- name: get_books?
+-- param: sort_col: ident(title, b.loaned_on) - sort column
+SELECT * FROM books b ORDER BY :!sort_col
+
+-- name: get_loans?
+-- param: sort_col: ident(title, b.loaned_on)
+SELECT * FROM loans b ORDER BY :!sort_col DESC
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [@ sort_col (LibrarySqlSortCol)]"), "{}", call);
+        assert!(call.contains("text = [\"SELECT * FROM books b ORDER BY \" @ sort_col]"), "{}", call);
+
+        let sql = crate::sql::parse(text, "library").unwrap();
+        let mut tokens = TokenStream::new();
+        sql.to_param_types_tokens(&mut tokens);
+        let types = tokens.to_string();
+        assert_eq!(types.matches("pub enum LibrarySqlSortCol").count(), 1, "{}", types);
+        assert!(types.contains("pub enum LibrarySqlSortCol { Title , BLoanedOn }"), "{}", types);
+        assert!(types.contains("Self :: Title => \"title\" , Self :: BLoanedOn => \"b.loaned_on\""), "{}", types);
+    }
+
+    #[test]
+    fn enum_params() {
+        let text = "
+-- name: set_status!
+-- param: status: enum(active, on-hold) - account status
+UPDATE accounts SET status = :status WHERE id = :id
+
+-- name: find_accounts?
+-- param: status: enum(active, on-hold)
+SELECT * FROM accounts WHERE status = :status?
+
+-- name: find_games?
+-- param: result: enum(win, loss)
+SELECT * FROM games WHERE result = :result
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [% status (LibrarySqlStatus) : id _]"), "{}", call);
+        assert!(call.contains("params = [% status (Option < LibrarySqlStatus >)]"), "{}", call);
+
+        let sql = crate::sql::parse(text, "library").unwrap();
+        let mut tokens = TokenStream::new();
+        sql.to_param_types_tokens(&mut tokens);
+        let types = tokens.to_string();
+        assert_eq!(types.matches("pub enum LibrarySqlStatus").count(), 1, "{}", types);
+        assert!(types.contains("pub enum LibrarySqlStatus { Active , OnHold }"), "{}", types);
+        assert!(types.contains("Self :: Active => \"active\" , Self :: OnHold => \"on-hold\""), "{}", types);
+        assert!(types.contains("impl :: std :: fmt :: Display for LibrarySqlStatus"), "{}", types);
+        assert!(types.contains("pub enum LibrarySqlResult { Win , Loss }"), "{}", types);
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn param_order() {
+        let text = "
+-- name: loan_books!
+-- params: user_id, book_ids, loaned_on
+-- param: book_ids: i64 - book IDs
+-- param: loaned_on: &str - loan date
+UPDATE library SET loaned_to = :user_id, loaned_on = :loaned_on WHERE book_id IN (:book_ids)
+        ";
+        let call = impl_sql_call(text, Protocol::V1);
+        assert!(call.contains("! loan_books (: user_id _ # book_ids (i64) : loaned_on (& str))"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn param_order_async() {
+        let text = "
+-- name: loan_books!
+-- params: user_id, book_ids, loaned_on
+-- param: book_ids: i64 - book IDs
+-- param: loaned_on: &str - loan date
+UPDATE library SET loaned_to = :user_id, loaned_on = :loaned_on WHERE book_id IN (:book_ids)
+        ";
+        let call = impl_sql_call(text, Protocol::V1);
+        assert!(call.contains("! loan_books (: user_id _ # book_ids ('book_ids i64) : loaned_on ('loaned_on & str))"), "{}", call);
+    }
+
+    #[test]
+    fn param_defaults() {
+        let text = "
+-- name: find_books?
+-- param: status: &str = \"active\" - loan status
+-- param: limit: i64 = 100 - page size
+SELECT * FROM books WHERE status = :status LIMIT :limit
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("defaults = { status = (\"active\") , limit = (100) }"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(not(feature = "async"))]
+    fn null_safe_comparisons() {
+        let text = "
+-- name: find_books?
+-- param: author: Option<&str> - book author
+SELECT * FROM books WHERE author = :author AND editor != :editor?
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [: author (Option < & str >) : editor (Option < _ >)]"), "{}", call);
+        assert!(call.contains("text = [\"SELECT * FROM books WHERE author \" == author \" AND editor \" != editor]"), "{}", call);
+
+        let text = "
+-- name: find_books?
+-- param: author: Option<&str> - book author
+SELECT * FROM books WHERE author = :author
+        ";
+        let call = impl_sql_call(text, Protocol::V1);
+        assert!(call.contains("$ \"SELECT * FROM books WHERE author = \" : author }"), "{}", call);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn null_safe_comparisons_async() {
+        let text = "
+-- name: find_books?
+-- param: author: &str - book author
+-- param: editor: Option<&str> - book editor
+SELECT * FROM books WHERE author = :author? AND editor != :editor
+        ";
+        let call = impl_sql_call(text, Protocol::V3);
+        assert!(call.contains("params = [: author (Option < & 'author str >) : editor (Option < & 'editor str >)]"), "{}", call);
+        assert!(call.contains("text = [\"SELECT * FROM books WHERE author \" == author \" AND editor \" != editor]"), "{}", call);
+    }
+}
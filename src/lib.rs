@@ -1,10 +1,9 @@
 #![cfg_attr(docsrs, doc = include_str!("../README.md"))]
 
-use std::{fs, path::PathBuf};
+use std::{fs, path::{Path, PathBuf}};
 
-use proc_macro;
 use proc_macro2::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream};
-use quote::{ToTokens, TokenStreamExt};
+use quote::TokenStreamExt;
 use syn::{
     self,
     parse::{Parse, ParseStream},
@@ -14,6 +13,7 @@ use syn::{
 mod conv;
 mod err;
 mod gen;
+mod scan;
 mod sql;
 
 /**
@@ -71,6 +71,18 @@ Where:
 
 > **Note** also that parameter order is defined by the `param` declarations. SQL parameters that are present in the SQL code, but that are not declared as one of the `param`s, will be follow the `param` parameters in the order they are found in the SQL code.
 
+## Options
+
+The SQL file path can be followed by comma separated `name = value` options:
+
+```rust,ignore
+include_sql!("/sql/library.sql", protocol = 3, dialect = postgres, infer_variant = true);
+```
+
+* `protocol` selects the version of the `impl_sql` input protocol - `1` (the default), `2` or `3`. Version 2 adds the file doc-comment after the generated name. Version 3 passes statements as keyword-tagged arguments and is required by most of the statement features beyond names, parameters and doc-comments, like statement attributes or result columns.
+* `dialect` selects the statement variants that were declared for the specified dialect, like `-- name: upsert_user! [postgres]`. Statements without such a variant use their default one. Without this option all variants are passed to `impl_sql`, which requires protocol version 3.
+* `infer_variant` - when `true` - makes include-sql infer omitted statement variant tags from the leading keyword of the statement, like `?` for `SELECT`, instead of using `!`. In this mode explicit tags that contradict the SQL are reported as deprecation warnings.
+
 ## Async
 
 When include-sql is built with the `async` feature, `impl_sql` macro will be generated with additional lifetimes for reference parameters.
@@ -119,20 +131,46 @@ impl_sql!{ LibrarySql =
 }
 ```
 
+**Note** also that references that are passed as `Option`s - parameters declared as `Option<&T>`, parameters that are marked as nullable by `:param?`, and parameters that are used only in optional fragments - cannot be preceded by the lifetime. For them the lifetime is set on the reference itself, like `(Option<&'status str>)`.
+
 [1]: https://crates.io/crates/include-postgres-sql
 [2]: https://crates.io/crates/include-sqlite-sql
 */
 #[proc_macro]
 pub fn include_sql(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let inc_file_lit = syn::parse_macro_input!(input as syn::LitStr);
+    let IncludeSqlArgs {
+        inc_file_lit,
+        dialect,
+        infer_variant,
+        protocol,
+    } = syn::parse_macro_input!(input as IncludeSqlArgs);
     let inc_file_path = local_file(&inc_file_lit);
+    let dialect = dialect.map(|dialect| dialect.to_string());
+
+    let mut sql_files = Vec::new();
+    let mut warnings = Vec::new();
+    let included_sql = read_and_parse_sql_file(&inc_file_path, &mut sql_files)
+        .and_then(|mut included_sql| {
+            included_sql.select_dialect(dialect.as_deref())?;
+            if infer_variant {
+                warnings = included_sql.infer_variants();
+            }
+            Ok(included_sql)
+        })
+        .and_then(|included_sql| included_sql.check_protocol(protocol).map(|_| included_sql));
 
-    match read_and_parse_sql_file(&inc_file_path) {
+    match included_sql {
         Ok(included_sql) => {
             let mut tokens = TokenStream::new();
-            output_include_bytes(&inc_file_path, &mut tokens);
+            for sql_file in &sql_files {
+                output_include_bytes(sql_file, &mut tokens);
+            }
+            for warning in &warnings {
+                output_warning(warning, &mut tokens);
+            }
             if !included_sql.stmt_list.is_empty() {
-                included_sql.to_tokens(&mut tokens);
+                included_sql.to_param_types_tokens(&mut tokens);
+                included_sql.to_impl_sql_tokens(protocol, &mut tokens);
             }
             tokens.into()
         }
@@ -142,6 +180,47 @@ pub fn include_sql(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     }
 }
 
+struct IncludeSqlArgs {
+    inc_file_lit: LitStr,
+    dialect: Option<syn::Ident>,
+    infer_variant: bool,
+    protocol: gen::Protocol,
+}
+
+impl Parse for IncludeSqlArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let inc_file_lit = input.parse()?;
+        let mut dialect = None;
+        let mut infer_variant = false;
+        let mut protocol = gen::Protocol::default();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            if option == "dialect" {
+                dialect = Some(input.parse()?);
+            } else if option == "infer_variant" {
+                infer_variant = input.parse::<syn::LitBool>()?.value;
+            } else if option == "protocol" {
+                let version: syn::LitInt = input.parse()?;
+                protocol = gen::Protocol::from_version(version.base10_parse()?)
+                    .ok_or_else(|| syn::Error::new(version.span(), "unsupported impl_sql protocol version"))?;
+            } else {
+                return Err(syn::Error::new(option.span(), "unknown include_sql option"));
+            }
+        }
+        Ok(Self {
+            inc_file_lit,
+            dialect,
+            infer_variant,
+            protocol,
+        })
+    }
+}
+
 /// Returns path to the included SQL file on the local file system.
 ///
 /// If the path given to the `include_sql` macro is relative, i.e. it does not start with `/`,
@@ -151,46 +230,95 @@ pub fn include_sql(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// considers it to be relative to the "root" of the project, i.e. relative to the
 /// `CARGO_MANIFEST_DIR` directory.
 fn local_file(inc_file_lit_path: &LitStr) -> PathBuf {
+    let inc_mod = inc_file_lit_path.span().unwrap().file();
+    let inc_dir = if inc_mod.is_empty() {
+        None
+    } else {
+        let mut inc_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
+        inc_dir.push(inc_mod);
+        inc_dir.pop();
+        Some(inc_dir)
+    };
+    resolve_path(&inc_file_lit_path.value(), inc_dir.as_deref())
+}
+
+/// Resolves the path of an included or imported file.
+///
+/// Explicitly relative paths, i.e. those that start with `./`, are resolved against `rel_dir`.
+/// All other paths are resolved against the `CARGO_MANIFEST_DIR` directory.
+fn resolve_path(file_path: &str, rel_dir: Option<&Path>) -> PathBuf {
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR");
     let mut path = PathBuf::from(&manifest_dir);
 
-    let inc_file_path = inc_file_lit_path.value();
-    if inc_file_path.starts_with('/') {
+    if let Some(file_path) = file_path.strip_prefix('/') {
         // Explicit absolute path
-        path.push(&inc_file_path[1..]);
-    } else if inc_file_path.starts_with("./") {
+        path.push(file_path);
+    } else if let Some(file_path) = file_path.strip_prefix("./") {
         // Explicit relative path
-        let inc_mod = inc_file_lit_path.span().unwrap().file();
-        if !inc_mod.is_empty() {
-            path.push(inc_mod);
-            path.pop();
+        if let Some(rel_dir) = rel_dir {
+            path.push(rel_dir);
         }
-        path.push(&inc_file_path[2..]);
+        path.push(file_path);
     } else {
         // Implicit absolute path
-        path.push(inc_file_path);
+        path.push(file_path);
     }
     path
 }
 
 /// Reads the content of the file at the `path` and parses its content.
-fn read_and_parse_sql_file(file_path: &PathBuf) -> err::Result<sql::IncludedSql> {
-    // let text = fs::read_to_string(&file_path)?;
-    let text = match fs::read_to_string(&file_path) {
-        Ok(text) => text,
-        Err(_) => return Err(err::Error::IO(std::io::Error::other(file_path.to_str().unwrap())))
-    };
+///
+/// Paths of the included file and all the files it imports are added to `sql_files`.
+fn read_and_parse_sql_file(file_path: &Path, sql_files: &mut Vec<PathBuf>) -> err::Result<sql::IncludedSql> {
+    let sql_text = read_sql_text(file_path, &mut Vec::new(), sql_files)?;
     let file_name = file_path
         .file_stem()
         .unwrap_or_default()
         .to_str()
         .unwrap_or_default()
         .replace('-', "_");
-    sql::parse(&text, &file_name)
+    sql_text.into_included_sql(&file_name)
+}
+
+/// Reads and parses the SQL file and merges into it the files it imports.
+///
+/// `importing` is the chain of files that are being imported, which is used to detect import cycles.
+fn read_sql_text(file_path: &Path, importing: &mut Vec<PathBuf>, sql_files: &mut Vec<PathBuf>) -> err::Result<sql::SqlText> {
+    let text = match fs::read_to_string(file_path) {
+        Ok(text) => text,
+        Err(_) => return Err(err::Error::IO(std::io::Error::other(file_path.to_str().unwrap())))
+    };
+    let file_path = fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+    sql_files.push(file_path.clone());
+
+    let mut sql_text = sql::parse_text(&text)?;
+    importing.push(file_path.clone());
+    for import in sql_text.imports().to_vec() {
+        let import_path = resolve_path(&import, file_path.parent());
+        let import_path = fs::canonicalize(&import_path).unwrap_or(import_path);
+        if importing.contains(&import_path) {
+            let cycle = importing.iter()
+                .skip_while(|path| **path != import_path)
+                .chain(std::iter::once(&import_path))
+                .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(err::new(format!("import cycle: {}", cycle)));
+        }
+        if sql_files.contains(&import_path) {
+            // has been imported already by another file
+            continue;
+        }
+        let imported = read_sql_text(&import_path, importing, sql_files)
+            .map_err(|import_err| err::new(format!("in `{}` imported by `{}`: {}", import, file_path.file_name().unwrap_or_default().to_string_lossy(), import_err)))?;
+        sql_text.import(imported);
+    }
+    importing.pop();
+    Ok(sql_text)
 }
 
 /// Writes a phantom call to `include_bytes` to make compiler aware of the external dependency.
-fn output_include_bytes(file_path: &PathBuf, tokens: &mut TokenStream) {
+fn output_include_bytes(file_path: &Path, tokens: &mut TokenStream) {
     tokens.append(Ident::new("const", Span::call_site()));
     tokens.append(Ident::new("_", Span::call_site()));
     tokens.append(Punct::new(':', Spacing::Alone));
@@ -211,6 +339,21 @@ fn output_include_bytes(file_path: &PathBuf, tokens: &mut TokenStream) {
     tokens.append(Punct::new(';', Spacing::Alone));
 }
 
+/// Writes a use of a deprecated item to make the compiler report the warning.
+///
+/// Procedural macros cannot emit warnings on stable Rust, thus the warning is reported
+/// as a deprecation note.
+fn output_warning(msg: &str, tokens: &mut TokenStream) {
+    tokens.extend(quote::quote! {
+        const _: () = {
+            #[deprecated(note = #msg)]
+            #[allow(non_camel_case_types)]
+            struct include_sql_warning;
+            let _ = include_sql_warning;
+        };
+    });
+}
+
 /**
 Finds the specified item (`ident`) in a list (of `idents`).
 
@@ -264,3 +407,67 @@ impl Parse for IndexOfArgs {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    /// Writes the SQL files into a fresh test directory under `CARGO_MANIFEST_DIR`.
+    fn write_sql_files(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let mut dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
+        dir.push("target");
+        dir.push("import_tests");
+        dir.push(test_name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (file_name, text) in files {
+            fs::write(dir.join(file_name), text).unwrap();
+        }
+        fs::canonicalize(dir).unwrap()
+    }
+
+    #[test]
+    fn import_cycle() {
+        let dir = write_sql_files("import_cycle", &[
+            ("a.sql", "-- import: ./b.sql\n-- name: get_a?\nSELECT a FROM t"),
+            ("b.sql", "-- import: ./a.sql\n-- name: get_b?\nSELECT b FROM t"),
+        ]);
+        let mut sql_files = Vec::new();
+        let res = super::read_and_parse_sql_file(&dir.join("a.sql"), &mut sql_files);
+        assert_eq!(res.unwrap_err().to_string(), "in `./b.sql` imported by `a.sql`: import cycle: a.sql -> b.sql -> a.sql");
+    }
+
+    #[test]
+    fn diamond_import() {
+        let dir = write_sql_files("diamond_import", &[
+            ("a.sql", "-- import: ./b.sql\n-- import: ./c.sql\n-- name: get_a?\nSELECT a FROM t"),
+            ("b.sql", "-- import: ./d.sql\n-- name: get_b?\nSELECT b FROM t"),
+            ("c.sql", "-- import: ./d.sql\n-- name: get_c?\nSELECT c FROM t"),
+            ("d.sql", "-- name: get_d?\nSELECT d FROM t"),
+        ]);
+        let mut sql_files = Vec::new();
+        let sql = super::read_and_parse_sql_file(&dir.join("a.sql"), &mut sql_files).unwrap();
+        let names = sql.stmt_list.iter().map(|stmt| stmt.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["get_d", "get_b", "get_c", "get_a"]);
+        assert_eq!(sql_files, [dir.join("a.sql"), dir.join("b.sql"), dir.join("d.sql"), dir.join("c.sql")]);
+    }
+
+    #[test]
+    fn dependency_files() {
+        let dir = write_sql_files("dependency_files", &[
+            ("a.sql", "-- import: ./b.sql\n-- name: get_a?\nSELECT a FROM t"),
+            ("b.sql", "-- name: get_b?\nSELECT b FROM t"),
+        ]);
+        let mut sql_files = Vec::new();
+        super::read_and_parse_sql_file(&dir.join("a.sql"), &mut sql_files).unwrap();
+        let mut tokens = proc_macro2::TokenStream::new();
+        for sql_file in &sql_files {
+            super::output_include_bytes(sql_file, &mut tokens);
+        }
+        let tokens = tokens.to_string();
+        for file_name in ["a.sql", "b.sql"] {
+            let include = format!("const _ : & [u8] = include_bytes ! ({:?}) ;", dir.join(file_name).to_str().unwrap());
+            assert!(tokens.contains(&include), "{}", tokens);
+        }
+    }
+}
@@ -0,0 +1,537 @@
+/// Lexical element of an SQL statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    /// Keyword or unquoted identifier
+    Word(&'a str),
+    /// Quoted identifier without its quotes
+    Quoted(&'a str),
+    /// String or numeric literal
+    Literal,
+    /// Any other character, like `(`, `,` or `.`
+    Punct(char),
+}
+
+/// Splits the SQL statement text into tokens.
+///
+/// This is not a complete SQL lexer. It only knows enough to skip comments and literals,
+/// so that the words it returns are SQL keywords and identifiers.
+pub(crate) fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        match ch {
+            _ if ch.is_whitespace() || ch.is_control() => {},
+            '-' if matches!(chars.peek(), Some((_, '-'))) => {
+                // line comment
+                for (_, ch) in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            },
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                let mut prev = ' ';
+                for (_, ch) in chars.by_ref() {
+                    if prev == '*' && ch == '/' {
+                        break;
+                    }
+                    prev = ch;
+                }
+            },
+            '\'' => {
+                // string literal, where a quote is escaped by doubling it
+                while let Some((_, ch)) = chars.next() {
+                    if ch == '\'' {
+                        if matches!(chars.peek(), Some((_, '\''))) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                tokens.push(Token::Literal);
+            },
+            '"' | '`' => {
+                let end = text[start + 1..].find(ch).map(|pos| start + 1 + pos).unwrap_or(text.len());
+                tokens.push(Token::Quoted(&text[start + 1..end]));
+                while chars.next_if(|&(pos, _)| pos <= end).is_some() {}
+            },
+            '$' => {
+                // Postgres dollar-quoted string - $tag$...$tag$
+                let tag_len = text[start + 1..].find(|ch: char| !(ch.is_alphanumeric() || ch == '_')).unwrap_or(text.len() - start - 1);
+                let tag_end = start + 1 + tag_len;
+                if text[tag_end..].starts_with('$') {
+                    let tag = &text[start..=tag_end];
+                    let end = text[tag_end + 1..].find(tag).map(|pos| tag_end + 1 + pos + tag.len()).unwrap_or(text.len());
+                    tokens.push(Token::Literal);
+                    while chars.next_if(|&(pos, _)| pos < end).is_some() {}
+                } else {
+                    tokens.push(Token::Punct(ch));
+                }
+            },
+            _ if ch.is_ascii_digit() => {
+                while chars.next_if(|&(_, ch)| ch.is_alphanumeric() || ch == '.' || ch == '_').is_some() {}
+                tokens.push(Token::Literal);
+            },
+            _ if ch.is_alphabetic() || ch == '_' => {
+                let mut end = start + ch.len_utf8();
+                while let Some((pos, ch)) = chars.next_if(|&(_, ch)| ch.is_alphanumeric() || ch == '_' || ch == '$') {
+                    end = pos + ch.len_utf8();
+                }
+                tokens.push(Token::Word(&text[start..end]));
+            },
+            _ => {
+                tokens.push(Token::Punct(ch));
+            }
+        }
+    }
+    tokens
+}
+
+/// Kind of an SQL statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StmtClass {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    /// `MERGE` and upserts, like `INSERT ... ON CONFLICT`
+    Merge,
+    Ddl,
+    /// Stored procedure calls and anonymous blocks
+    Call,
+    /// Transaction control
+    Transaction,
+    Other,
+}
+
+impl StmtClass {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Select => "select",
+            Self::Insert => "insert",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Merge => "merge",
+            Self::Ddl => "ddl",
+            Self::Call => "call",
+            Self::Transaction => "transaction",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Classifies the statement by its leading keyword.
+pub(crate) fn classify(tokens: &[Token]) -> StmtClass {
+    let Some(verb) = leading_verb(tokens) else { return StmtClass::Other; };
+    match verb.to_ascii_uppercase().as_str() {
+        "SELECT" | "VALUES" | "TABLE" => StmtClass::Select,
+        "INSERT" => {
+            if has_word_sequence(tokens, &["ON", "CONFLICT"]) || has_word_sequence(tokens, &["ON", "DUPLICATE", "KEY"]) {
+                StmtClass::Merge
+            } else {
+                StmtClass::Insert
+            }
+        },
+        "UPDATE" => StmtClass::Update,
+        "DELETE" => StmtClass::Delete,
+        "MERGE" | "UPSERT" | "REPLACE" => StmtClass::Merge,
+        "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME" | "COMMENT" | "GRANT" | "REVOKE" => StmtClass::Ddl,
+        "CALL" | "EXEC" | "EXECUTE" | "DO" | "DECLARE" => StmtClass::Call,
+        "BEGIN" => {
+            // `BEGIN` either starts a transaction or an anonymous block
+            let is_block = tokens.iter().filter_map(|token| match token {
+                Token::Word(word) => Some(*word),
+                _ => None
+            }).nth(1).filter(|word| !["TRANSACTION", "WORK", "TRAN", "ISOLATION", "READ", "DEFERRED", "IMMEDIATE", "EXCLUSIVE"].iter().any(|kw| word.eq_ignore_ascii_case(kw))).is_some();
+            if is_block { StmtClass::Call } else { StmtClass::Transaction }
+        },
+        "START" | "COMMIT" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" | "END" | "ABORT" => StmtClass::Transaction,
+        "SET" if has_word_sequence(tokens, &["SET", "TRANSACTION"]) => StmtClass::Transaction,
+        _ => StmtClass::Other
+    }
+}
+
+/// Checks whether the statement only reads data.
+///
+/// Only queries that neither modify data (in a CTE or via `SELECT INTO`) nor lock
+/// selected rows (`FOR UPDATE`) are read-only. Functions that the query calls are
+/// not analyzed.
+pub(crate) fn is_read_only(class: StmtClass, tokens: &[Token]) -> bool {
+    class == StmtClass::Select
+        && !tokens.iter().any(|token| ["INSERT", "UPDATE", "DELETE", "MERGE", "SHARE"].iter().any(|kw| matches!(token, Token::Word(word) if word.eq_ignore_ascii_case(kw))))
+        && !top_level_words(tokens).any(|word| word.eq_ignore_ascii_case("INTO"))
+}
+
+/// Infers the statement variant selector from the statement text.
+///
+/// Returns `?` for queries, `->` for DML statements with a `RETURNING` clause,
+/// and `!` for other DML and DDL statements. Returns `None` when the statement
+/// is not recognized.
+pub(crate) fn infer_variant(class: StmtClass, tokens: &[Token]) -> Option<&'static str> {
+    match class {
+        StmtClass::Select => Some("?"),
+        StmtClass::Insert | StmtClass::Update | StmtClass::Delete | StmtClass::Merge => {
+            if top_level_words(tokens).any(|word| word.eq_ignore_ascii_case("RETURNING")) {
+                Some("->")
+            } else {
+                Some("!")
+            }
+        },
+        StmtClass::Ddl => Some("!"),
+        StmtClass::Other => {
+            let verb = leading_verb(tokens)?;
+            if verb.eq_ignore_ascii_case("SHOW") || verb.eq_ignore_ascii_case("EXPLAIN") { Some("?") } else { None }
+        },
+        StmtClass::Call | StmtClass::Transaction => None,
+    }
+}
+
+/// Keywords that start a query or a data-modifying statement
+const QUERY_VERBS: &[&str] = &["SELECT", "WITH", "VALUES", "TABLE", "INSERT", "UPDATE", "DELETE", "MERGE"];
+
+/// Keywords that might follow a table name, and thus cannot be its alias
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "WHERE", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "CROSS", "NATURAL", "OUTER", "ON", "USING",
+    "GROUP", "ORDER", "HAVING", "LIMIT", "OFFSET", "FETCH", "UNION", "INTERSECT", "EXCEPT", "MINUS",
+    "WINDOW", "FOR", "SET", "RETURNING", "VALUES", "SELECT", "DEFAULT", "WHEN", "AS", "OVERRIDING",
+];
+
+/// Finds tables that the statement reads and writes.
+///
+/// Tables are found after `FROM`, `JOIN`, `INTO`, `UPDATE`, `USING` (in `MERGE` and `DELETE`)
+/// and `TABLE` (in DDL). Names of common table expressions are not included.
+pub(crate) fn referenced_tables(class: StmtClass, tokens: &[Token]) -> (Vec<String>, Vec<String>) {
+    let ctes = cte_names(tokens);
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    // for the top level and each open parenthesis - whether it contains a query, rather than,
+    // say, function arguments, and whether its `FROM` clause is being read
+    let mut levels = vec![(true, false)];
+    let mut pos = 0;
+    while pos < tokens.len() {
+        let token = tokens[pos];
+        let prev = if pos > 0 { word_at(tokens, pos - 1) } else { None };
+        pos += 1;
+        let (in_query, in_from) = levels.last().copied().unwrap_or_default();
+        match token {
+            Token::Punct('(') => {
+                levels.push((word_at(tokens, pos).is_some_and(|word| is_one_of(word, QUERY_VERBS)), false));
+            },
+            Token::Punct(')') if levels.len() > 1 => {
+                levels.pop();
+            },
+            Token::Punct(',') if in_query && in_from => {
+                pos = read_tables(tokens, pos, TableRef::SourceList, &ctes, &mut reads);
+            },
+            Token::Word(word) if in_query => {
+                let is_prev = |keywords: &[&str]| prev.is_some_and(|prev| is_one_of(prev, keywords));
+                if word.eq_ignore_ascii_case("FROM") && !is_prev(&["DISTINCT"]) {
+                    if is_prev(&["DELETE"]) {
+                        pos = read_tables(tokens, pos, TableRef::Target, &ctes, &mut writes);
+                    } else {
+                        pos = read_tables(tokens, pos, TableRef::SourceList, &ctes, &mut reads);
+                        set_in_from(&mut levels, true);
+                    }
+                } else if word.eq_ignore_ascii_case("JOIN") {
+                    pos = read_tables(tokens, pos, TableRef::Source, &ctes, &mut reads);
+                } else if word.eq_ignore_ascii_case("USING") && matches!(class, StmtClass::Merge | StmtClass::Delete) {
+                    pos = read_tables(tokens, pos, TableRef::SourceList, &ctes, &mut reads);
+                } else if word.eq_ignore_ascii_case("TABLE") && class == StmtClass::Select && prev.is_none() {
+                    pos = read_tables(tokens, pos, TableRef::Target, &ctes, &mut reads);
+                } else if is_write_target(word, prev, word_at(tokens, pos), class) {
+                    pos = read_tables(tokens, pos, TableRef::Target, &ctes, &mut writes);
+                } else if in_from && is_one_of(word, FROM_END_KEYWORDS) {
+                    set_in_from(&mut levels, false);
+                }
+            },
+            _ => {}
+        }
+    }
+    (reads, writes)
+}
+
+/// Keywords that end the `FROM` clause
+const FROM_END_KEYWORDS: &[&str] = &[
+    "WHERE", "GROUP", "ORDER", "HAVING", "LIMIT", "OFFSET", "FETCH", "UNION", "INTERSECT", "EXCEPT",
+    "MINUS", "WINDOW", "FOR", "RETURNING", "SELECT", "CONNECT", "START",
+];
+
+fn set_in_from(levels: &mut [(bool, bool)], in_from: bool) {
+    if let Some((_, level_in_from)) = levels.last_mut() {
+        *level_in_from = in_from;
+    }
+}
+
+/// Checks whether the keyword is followed by the name of the table that the statement modifies.
+fn is_write_target(word: &str, prev: Option<&str>, next: Option<&str>, class: StmtClass) -> bool {
+    if word.eq_ignore_ascii_case("INTO") {
+        true
+    } else if word.eq_ignore_ascii_case("UPDATE") {
+        // not `FOR UPDATE`, `DO UPDATE SET`, `ON UPDATE CASCADE`, etc
+        !prev.is_some_and(|prev| is_one_of(prev, &["FOR", "DO", "ON", "KEY", "NO"]))
+            && !next.is_some_and(|next| next.eq_ignore_ascii_case("SET"))
+    } else if word.eq_ignore_ascii_case("TABLE") {
+        class == StmtClass::Ddl
+    } else if word.eq_ignore_ascii_case("TRUNCATE") {
+        class == StmtClass::Ddl && !next.is_some_and(|next| next.eq_ignore_ascii_case("TABLE"))
+    } else {
+        false
+    }
+}
+
+/// How a table is referenced by the statement
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TableRef {
+    /// A single table that the statement modifies
+    Target,
+    /// A single table that the statement reads
+    Source,
+    /// A comma separated list of tables that the statement reads
+    SourceList,
+}
+
+/// Reads table names that start at the specified position and adds them to the list.
+///
+/// Returns the position after the last table name (and its alias).
+fn read_tables(tokens: &[Token], mut pos: usize, table_ref: TableRef, ctes: &[&str], tables: &mut Vec<String>) -> usize {
+    loop {
+        while word_at(tokens, pos).is_some_and(|word| is_one_of(word, &["ONLY", "LATERAL", "IF", "NOT", "EXISTS"])) {
+            pos += 1;
+        }
+        let Some((name, next)) = qualified_name(tokens, pos) else { return pos; };
+        if table_ref != TableRef::Target && tokens.get(next) == Some(&Token::Punct('(')) {
+            // a table function
+            return next;
+        }
+        if !ctes.iter().any(|cte| cte.eq_ignore_ascii_case(&name)) && !tables.contains(&name) {
+            tables.push(name);
+        }
+        pos = next;
+        if table_ref != TableRef::SourceList {
+            return pos;
+        }
+        // skip the alias
+        if word_at(tokens, pos).is_some_and(|word| word.eq_ignore_ascii_case("AS")) {
+            pos += 2;
+        } else if word_at(tokens, pos).is_some_and(|word| !is_one_of(word, CLAUSE_KEYWORDS)) || matches!(tokens.get(pos), Some(Token::Quoted(_))) {
+            pos += 1;
+        }
+        if tokens.get(pos) != Some(&Token::Punct(',')) {
+            return pos;
+        }
+        pos += 1;
+    }
+}
+
+/// Reads a possibly qualified name, like `schema.table`.
+///
+/// Returns the name and the position after it.
+fn qualified_name(tokens: &[Token], mut pos: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    loop {
+        match tokens.get(pos) {
+            Some(Token::Word(part)) | Some(Token::Quoted(part)) => name.push_str(part),
+            _ => return None
+        }
+        pos += 1;
+        if tokens.get(pos) != Some(&Token::Punct('.')) {
+            return Some((name, pos));
+        }
+        name.push('.');
+        pos += 1;
+    }
+}
+
+/// Returns names of common table expressions defined by the statement.
+fn cte_names<'a>(tokens: &[Token<'a>]) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    for (with_pos, token) in tokens.iter().enumerate() {
+        if !matches!(token, Token::Word(word) if word.eq_ignore_ascii_case("WITH")) {
+            continue;
+        }
+        let mut pos = with_pos + 1;
+        if word_at(tokens, pos).is_some_and(|word| word.eq_ignore_ascii_case("RECURSIVE")) {
+            pos += 1;
+        }
+        while let Some(Token::Word(name) | Token::Quoted(name)) = tokens.get(pos) {
+            pos += 1;
+            if tokens.get(pos) == Some(&Token::Punct('(')) {
+                // column list
+                pos = skip_parens(tokens, pos);
+            }
+            if !word_at(tokens, pos).is_some_and(|word| word.eq_ignore_ascii_case("AS")) {
+                break;
+            }
+            pos += 1;
+            while word_at(tokens, pos).is_some_and(|word| is_one_of(word, &["NOT", "MATERIALIZED"])) {
+                pos += 1;
+            }
+            if tokens.get(pos) != Some(&Token::Punct('(')) {
+                break;
+            }
+            names.push(*name);
+            pos = skip_parens(tokens, pos);
+            if tokens.get(pos) != Some(&Token::Punct(',')) {
+                break;
+            }
+            pos += 1;
+        }
+    }
+    names
+}
+
+/// Returns the position after the parenthesis that closes the one at the specified position.
+fn skip_parens(tokens: &[Token], mut pos: usize) -> usize {
+    let mut depth = 0usize;
+    while let Some(token) = tokens.get(pos) {
+        pos += 1;
+        match token {
+            Token::Punct('(') => depth += 1,
+            Token::Punct(')') => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            },
+            _ => {}
+        }
+    }
+    pos
+}
+
+fn word_at<'a>(tokens: &[Token<'a>], pos: usize) -> Option<&'a str> {
+    match tokens.get(pos) {
+        Some(Token::Word(word)) => Some(*word),
+        _ => None
+    }
+}
+
+fn is_one_of(word: &str, keywords: &[&str]) -> bool {
+    keywords.iter().any(|keyword| word.eq_ignore_ascii_case(keyword))
+}
+
+/// Checks whether the statement contains the specified sequence of words.
+fn has_word_sequence(tokens: &[Token], words: &[&str]) -> bool {
+    tokens.windows(words.len()).any(|window| {
+        window.iter().zip(words).all(|(token, word)| matches!(token, Token::Word(token_word) if token_word.eq_ignore_ascii_case(word)))
+    })
+}
+
+/// Returns the keyword that defines what the statement does.
+///
+/// For statements with common table expressions this is the first keyword of
+/// the main statement that follows them.
+pub(crate) fn leading_verb<'a>(tokens: &[Token<'a>]) -> Option<&'a str> {
+    let first = tokens.iter().find_map(|token| match token {
+        Token::Word(word) => Some(*word),
+        _ => None
+    })?;
+    if !first.eq_ignore_ascii_case("WITH") {
+        return Some(first);
+    }
+    top_level_words(tokens).find(|word| {
+        ["SELECT", "VALUES", "INSERT", "UPDATE", "DELETE", "MERGE"].iter().any(|verb| word.eq_ignore_ascii_case(verb))
+    })
+}
+
+/// Returns words that are not nested in parentheses.
+pub(crate) fn top_level_words<'a, 't>(tokens: &'t [Token<'a>]) -> impl Iterator<Item = &'a str> + 't {
+    let mut depth = 0usize;
+    tokens.iter().filter_map(move |token| {
+        match token {
+            Token::Punct('(') => depth += 1,
+            Token::Punct(')') => depth = depth.saturating_sub(1),
+            Token::Word(word) if depth == 0 => return Some(*word),
+            _ => {}
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_skips_comments_and_literals() {
+        let text = "SELECT 'it''s', $$ returning $$, \"User Name\" -- returning\n FROM /* returning */ t WHERE x = :x";
+        let tokens = tokenize(text);
+        assert_eq!(tokens, [
+            Token::Word("SELECT"), Token::Literal, Token::Punct(','), Token::Literal, Token::Punct(','),
+            Token::Quoted("User Name"), Token::Word("FROM"), Token::Word("t"), Token::Word("WHERE"),
+            Token::Word("x"), Token::Punct('='), Token::Punct(':'), Token::Word("x"),
+        ]);
+    }
+
+    #[test]
+    fn infer_statement_variant() {
+        let infer = |text| {
+            let tokens = tokenize(text);
+            infer_variant(classify(&tokens), &tokens)
+        };
+        assert_eq!(infer("SELECT * FROM users"), Some("?"));
+        assert_eq!(infer("with recursive t (n) AS (SELECT 1) select n from t"), Some("?"));
+        assert_eq!(infer("WITH d AS (DELETE FROM users RETURNING id) SELECT count(*) FROM d"), Some("?"));
+        assert_eq!(infer("WITH s AS (SELECT 1) INSERT INTO t SELECT * FROM s"), Some("!"));
+        assert_eq!(infer("INSERT INTO users (name) VALUES (:name) RETURNING id"), Some("->"));
+        assert_eq!(infer("UPDATE users SET name = 'returning' WHERE id = :id"), Some("!"));
+        assert_eq!(infer("CREATE TABLE users (id INT)"), Some("!"));
+        assert_eq!(infer("BEGIN"), None);
+    }
+
+    #[test]
+    fn classify_statements() {
+        let class = |text| {
+            let tokens = tokenize(text);
+            let class = classify(&tokens);
+            (class.as_str(), is_read_only(class, &tokens))
+        };
+        assert_eq!(class("SELECT * FROM users"), ("select", true));
+        assert_eq!(class("WITH t AS (SELECT 1) SELECT * FROM t"), ("select", true));
+        assert_eq!(class("WITH d AS (DELETE FROM users RETURNING id) SELECT count(*) FROM d"), ("select", false));
+        assert_eq!(class("SELECT * FROM users WHERE id = :id FOR UPDATE"), ("select", false));
+        assert_eq!(class("SELECT * INTO backup FROM users"), ("select", false));
+        assert_eq!(class("INSERT INTO users (name) VALUES (:name)"), ("insert", false));
+        assert_eq!(class("INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO NOTHING"), ("merge", false));
+        assert_eq!(class("update users set name = :name"), ("update", false));
+        assert_eq!(class("DELETE FROM users"), ("delete", false));
+        assert_eq!(class("MERGE INTO users USING src ON users.id = src.id"), ("merge", false));
+        assert_eq!(class("CREATE INDEX users_name ON users (name)"), ("ddl", false));
+        assert_eq!(class("CALL add_user(:name)"), ("call", false));
+        assert_eq!(class("BEGIN add_user(:name); END;"), ("call", false));
+        assert_eq!(class("BEGIN TRANSACTION"), ("transaction", false));
+        assert_eq!(class("COMMIT"), ("transaction", false));
+        assert_eq!(class("VACUUM"), ("other", false));
+    }
+
+    #[test]
+    fn find_referenced_tables() {
+        let tables = |text| {
+            let tokens = tokenize(text);
+            referenced_tables(classify(&tokens), &tokens)
+        };
+        let strs = |list: &[&str]| list.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert_eq!(tables("SELECT b.title, u.name FROM books b JOIN lib.users AS u ON u.id = b.loaned_to, \"Loans\" l WHERE extract(year FROM b.published) = 2000"),
+            (strs(&["books", "lib.users", "Loans"]), strs(&[])));
+        assert_eq!(tables("WITH recent AS (SELECT * FROM loans WHERE loaned_on > :since) SELECT * FROM recent JOIN books USING (book_id)"),
+            (strs(&["loans", "books"]), strs(&[])));
+        assert_eq!(tables("SELECT * FROM generate_series(1, 10) AS s WHERE EXISTS (SELECT 1 FROM books)"),
+            (strs(&["books"]), strs(&[])));
+        assert_eq!(tables("INSERT INTO loans (book_id, user_id) SELECT book_id, :user_id FROM books WHERE title = :title ON CONFLICT (book_id) DO UPDATE SET user_id = excluded.user_id"),
+            (strs(&["books"]), strs(&["loans"])));
+        assert_eq!(tables("UPDATE ONLY books SET loaned_to = u.id FROM users u WHERE u.name = :name"),
+            (strs(&["users"]), strs(&["books"])));
+        assert_eq!(tables("DELETE FROM loans USING books WHERE loans.book_id = books.id"),
+            (strs(&["books"]), strs(&["loans"])));
+        assert_eq!(tables("MERGE INTO books USING new_books n ON books.id = n.id WHEN MATCHED THEN UPDATE SET title = n.title"),
+            (strs(&["new_books"]), strs(&["books"])));
+        assert_eq!(tables("SELECT * FROM books WHERE id = :id FOR UPDATE"),
+            (strs(&["books"]), strs(&[])));
+        assert_eq!(tables("CREATE TABLE IF NOT EXISTS archive AS SELECT * FROM books"),
+            (strs(&["books"]), strs(&["archive"])));
+        assert_eq!(tables("TRUNCATE loans"),
+            (strs(&[]), strs(&["loans"])));
+    }
+}
@@ -2,85 +2,268 @@
 
 use once_cell::sync::Lazy;
 use regex::Regex;
+use syn::parse::Parser;
+use quote::ToTokens;
 use crate::err::{self, Result};
-use crate::conv::to_snake_case;
+use crate::conv::{to_snake_case, StringExt};
+use crate::scan::{self, StmtClass};
 
 /// Parses the content of the included SQL file.
 pub(super) fn parse(text: &str, file_name: &str) -> Result<IncludedSql> {
-    let file_name = file_name.to_string();
-    let stmt_list = parse_text(text);
-    check_stmt_names(&stmt_list)?;
-    check_parameters(&stmt_list)?;
-    Ok(IncludedSql { file_name, stmt_list })
+    let sql_text = parse_text(text)?;
+    if let Some(import) = sql_text.imports.first() {
+        return Err(err::new(format!("cannot import `{}` without reading the file", import)));
+    }
+    sql_text.into_included_sql(file_name)
 }
 
 static LINE_COMMENT : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*--").expect("full line comment pattern"));
 static TAIL_COMMENT : Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*--").expect("line tail comment pattern"));
-static STMT_NAME    : Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*name:\s*([[:alpha:]][[:word:]]*)\s*([!#$%&*+./:<=>?@^|~-]*)").expect("statement name pattern"));
-static STMT_PARAM   : Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*param:\s*([[:alpha:]][[:word:]]*)\s*:\s*(\S+)\s*(.*)").expect("statement parameter pattern"));
+static STMT_NAME    : Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*name:\s*([[:alpha:]][[:word:]]*)(?:\s+:(one|opt|many|exec|execrows)\b|\s*([!#$%&*+./:<=>?@^|~-]*)([0-9]+(?:\.\.[0-9]+)?)?(?:\s+:(one|opt|many|exec|execrows)\b)?)(?:\s*\[\s*([[:alpha:]][[:word:]]*)\s*\])?").expect("statement name pattern"));
+static STMT_PARAM   : Lazy<Regex> = Lazy::new(|| Regex::new(r"\s*param:\s*([[:alpha:]][[:word:]]*)\s*:\s*(?:(in|out|inout)\s+)?([[:word:]]*\([^()]*\)|\S+)\s*(.*)").expect("statement parameter pattern"));
+static IDENT_TYPE   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^ident\(([^()]*)\)$").expect("identifier parameter type pattern"));
+static VALUE_TYPE   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^enum\(([^()]*)\)$").expect("enumerated parameter type pattern"));
+static VALUE_NAME   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^[[:alpha:]_][[:word:]]*(?:-[[:word:]]+)*$").expect("enumerated value pattern"));
+static IDENT_NAME   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^[[:alpha:]_][[:word:]]*(?:\.[[:alpha:]_][[:word:]]*)*$").expect("identifier name pattern"));
+static PARAM_CONT   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s{2,}(\S.*)$").expect("parameter description continuation pattern"));
+static STMT_COLUMNS : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(?:returns|column):\s*([[:alpha:]_][[:word:]]*\s*:.*)$").expect("result columns pattern"));
+static STMT_ORDER   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*params:\s*(.*)$").expect("parameter order pattern"));
+static STMT_TABLES  : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*tables:\s*((?:reads|writes)\s*=.*)$").expect("referenced tables pattern"));
+static TABLE_NAME   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^[[:alpha:]_][[:word:]$]*(?:\.[[:alpha:]_][[:word:]$]*)*$").expect("table name pattern"));
+static STMT_FRAGMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*fragment:\s*([[:alpha:]][[:word:]]*)\s*$").expect("fragment name pattern"));
+static TYPE_ALIAS   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*type:\s*(\S+?)\s*=\s*(.*?)\s*$").expect("type alias pattern"));
+static IMPORT_LINE  : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*import:\s*(\S+)\s*$").expect("file import pattern"));
+static INCLUDE_LINE : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*include:\s*([[:alpha:]][[:word:]]*)\s*$").expect("fragment include pattern"));
+static FRAGMENT_REF : Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*([[:alpha:]][[:word:]]*)\s*\}\}").expect("fragment reference pattern"));
+static RUST_ATTR    : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(#\[.*\])$").expect("Rust attribute pattern"));
+static STMT_CFG     : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*cfg:\s*(.+)$").expect("statement cfg pattern"));
+static CFG_IF       : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*#if\s+(.+)$").expect("conditional fragment start pattern"));
+static CFG_ENDIF    : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*#endif\s*$").expect("conditional fragment end pattern"));
+static OPT_IF       : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\?\s*if\s+:([[:alpha:]][[:word:]]*)\s*$").expect("optional fragment start pattern"));
+static OPT_END      : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\?\s*end\s*$").expect("optional fragment end pattern"));
+static STMT_ATTR    : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*(?:@([[:alpha:]][[:word:]]*)\s*:(.*)|attr:\s*([[:alpha:]][[:word:]]*)\s*(?:=(.*))?)$").expect("statement attribute pattern"));
 // the non-IN parameter placeholder cheats a bit to avoid matching Postgres ::
 // casts -- we know a parameter will _never_ be at position zero in a valid
 // SQL statement, so we can use a negated class to exclude anything with two
 // colons. Much easier since lookaround isn't supported!
-static BIND_NAME    : Lazy<Regex> = Lazy::new(|| Regex::new(r"\b[Ii][Nn]\s*\(\s*(:[[:alpha:]][[:word:]]*)\s*\)|[^:](:[[:alpha:]][[:word:]]*)").expect("parameter placeholder pattern"));
+static BIND_NAME    : Lazy<Regex> = Lazy::new(|| Regex::new(r"\b[Ii][Nn]\s*\(\s*(:[[:alpha:]][[:word:]]*)\s*\)|[^:](:!?[[:alpha:]][[:word:]]*)(\?)?").expect("parameter placeholder pattern"));
+static FIELD_PATH   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?:\.[[:alpha:]][[:word:]]*)+").expect("struct field path pattern"));
+static TUPLE_FIELDS : Lazy<Regex> = Lazy::new(|| Regex::new(r"^\(\s*([[:alpha:]][[:word:]]*(?:\s*,\s*[[:alpha:]][[:word:]]*)*)\s*\)").expect("tuple fields pattern"));
 static INTO_TOKEN   : Lazy<Regex> = Lazy::new(|| Regex::new(r"^(?:[@,#$?;~_.]|[+^/*!%]=?|&[&=]?|=[=>]?|>[>=]?|<[<=-]?|[|][=|]?|-[=>]?|::?|[.][.][.=]?|>>=|<<=)$").expect("punctuation token pattern"));
 
-fn parse_text(text: &str) -> Vec<Stmt> {
-    let mut stmt_list = Vec::new();
-    let mut stmt_name = None;
-    let mut stmt_into = None;
+/// Marks the start of a conditional fragment in the statement text
+const GROUP_START : char = '\u{1}';
+/// Marks the end of a conditional fragment in the statement text
+const GROUP_END   : char = '\u{2}';
+
+/// Parses the content of the SQL file without expanding fragment references.
+///
+/// The returned text lists the files that this file imports. Statements and fragments
+/// of those files are expected to be merged in via `SqlText::import`.
+pub(crate) fn parse_text(text: &str) -> Result<SqlText> {
+    let mut sql_text = SqlText::default();
+    let mut file_docs = String::new();
+    let mut file_header = true;
+    let mut stmt_header = StmtHeader::default();
     let mut stmt_text = String::with_capacity(250);
-    let mut stmt_docs = String::with_capacity(250);
-    let mut stmt_params = Vec::new();
+    let mut type_aliases = TypeAliases::default();
+    // whether the preceding line is a `param:` line or its continuation
+    let mut param_continues = false;
 
     for line in text.lines() {
         let line = line.trim_end();
-        if line.len() == 0 { continue; }
+        if line.is_empty() { continue; }
 
         if let Some( comment_prefix ) = LINE_COMMENT.find(line) {
 
             let comment = &line[comment_prefix.end()..];
-            if let Some( name ) = STMT_NAME.captures( comment ) {
+            let continues_param = std::mem::take(&mut param_continues) && !is_meta_comment(comment);
+            if let Some( cont ) = PARAM_CONT.captures( comment ).filter(|_| continues_param) {
+                // an indented line that follows `param:` continues the parameter description
+                let mut cont_line = String::with_capacity(cont[1].len() + 4);
+                cont_line.push_str("\n   ");
+                cont_line.push_str(&cont[1]);
+                match sql_text.shared_params.last_mut().filter(|_| file_header) {
+                    Some((_, doc_line)) => doc_line.push_str(&cont_line),
+                    None => {
+                        stmt_header.docs.push_str(&cont_line);
+                        stmt_header.params_docs_end = Some(stmt_header.docs.len());
+                    },
+                }
+                param_continues = true;
+
+            } else if let Some( columns ) = STMT_COLUMNS.captures( comment ).filter(|_| stmt_text.is_empty()) {
+                // checked before `name:` as column declarations might include a `name:` column
+                for column in parse_columns(&columns[1])? {
+                    let rust_type = type_aliases.expand(&column.rust_type)?;
+                    stmt_header.columns.push(StmtColumn { rust_type, ..column });
+                }
+
+            } else if let Some( name ) = STMT_NAME.captures( comment ) {
+                file_header = false;
                 if !stmt_text.is_empty() {
                     // found a new name, while the current statement is not saved yet
-                    let stmt = Stmt::new(stmt_name, stmt_into, stmt_params, &stmt_text, &stmt_docs);
-                    stmt_list.push(stmt);
-
+                    sql_text.push(std::mem::take(&mut stmt_header), &stmt_text)?;
+                    stmt_text.clear();
+                }
+                let (rust_attrs, columns) = if stmt_header.name.is_none() {
+                    // Rust attributes and result columns that precede the `name:`
+                    (std::mem::take(&mut stmt_header.rust_attrs), std::mem::take(&mut stmt_header.columns))
+                } else {
+                    (Vec::new(), Vec::new())
+                };
+                stmt_header = StmtHeader::default();
+                stmt_header.rust_attrs = rust_attrs;
+                stmt_header.columns = columns;
+                let stmt_name = &name[1];
+                let selector = name.get(3).map(|into_match| into_match.as_str()).unwrap_or_default();
+                let count = name.get(4).map(|count_match| count_match.as_str());
+                let keyword = name.get(2).or(name.get(5)).map(|keyword_match| keyword_match.as_str());
+                let (into, cardinality) = split_cardinality(stmt_name, selector, count, keyword)?;
+                stmt_header.name = Some(stmt_name.to_string());
+                stmt_header.into = into;
+                stmt_header.cardinality = cardinality;
+                stmt_header.dialect = name.get(6).map(|dialect_match| dialect_match.as_str().to_string());
+
+            } else if let Some( fragment ) = STMT_FRAGMENT.captures( comment ) {
+                file_header = false;
+                if !stmt_text.is_empty() {
+                    sql_text.push(std::mem::take(&mut stmt_header), &stmt_text)?;
                     stmt_text.clear();
                 }
-                stmt_docs.clear();
-                stmt_params = Vec::new();
+                stmt_header = StmtHeader::default();
+                stmt_header.name = Some(fragment[1].to_string());
+                stmt_header.is_fragment = true;
+
+            } else if let Some( include ) = INCLUDE_LINE.captures( comment ) {
+                if !stmt_text.is_empty() {
+                    stmt_text.push('\n');
+                }
+                stmt_text.push_str("{{");
+                stmt_text.push_str(&include[1]);
+                stmt_text.push_str("}}");
+
+            } else if let Some( cond ) = CFG_IF.captures( comment ) {
+                let cfg_pred = cond[1].trim();
+                check_cfg_predicate(cfg_pred)?;
+                stmt_header.open_group(StmtGroup::Cfg(cfg_pred.to_string()));
+                stmt_text.push(GROUP_START);
+
+            } else if CFG_ENDIF.is_match( comment ) {
+                if !stmt_header.close_group(|group| matches!(group, StmtGroup::Cfg(_))) {
+                    return Err(err::new(format!("`#endif` without matching `#if` in `{}`", stmt_header.name.as_deref().unwrap_or_default())));
+                }
+                stmt_text.push(GROUP_END);
+
+            } else if let Some( cond ) = OPT_IF.captures( comment ) {
+                stmt_header.open_group(StmtGroup::Opt(to_snake_case(&cond[1])));
+                stmt_text.push(GROUP_START);
 
-                stmt_name = name.get(1).map(|name_match| name_match.as_str().to_string());
-                stmt_into = name.get(2).map(|into_match| into_match.as_str()).filter(|into| into.len() > 0).map(|into| into.to_string());
+            } else if OPT_END.is_match( comment ) {
+                if !stmt_header.close_group(|group| matches!(group, StmtGroup::Opt(_))) {
+                    return Err(err::new(format!("`--? end` without matching `--? if` in `{}`", stmt_header.name.as_deref().unwrap_or_default())));
+                }
+                stmt_text.push(GROUP_END);
 
             } else if !stmt_text.is_empty() {
+                if is_meta_comment(comment) && !stmt_text.contains(|c| c != GROUP_START && c != GROUP_END) {
+                    // the header line follows a conditional fragment start rather than the statement text
+                    return Err(err::new(format!("`{}` must be placed before the first conditional fragment of `{}`", comment.trim(), stmt_header.name.as_deref().unwrap_or_default())));
+                }
                 // Then the line is a statement inner comment
                 continue;
 
+            } else if let Some( import ) = IMPORT_LINE.captures( comment ) {
+                if !file_header {
+                    return Err(err::new(format!("`import: {}` must be placed in the file header before the first statement", &import[1])));
+                }
+                sql_text.imports.push(import[1].to_string());
+
+            } else if let Some( alias ) = TYPE_ALIAS.captures( comment ) {
+                if !file_header {
+                    return Err(err::new(format!("`type: {}` must be placed in the file header before the first statement", &alias[1])));
+                }
+                type_aliases.add(&alias[1], &alias[2])?;
+
+            } else if let Some( cfg ) = STMT_CFG.captures( comment ) {
+                let cfg_pred = cfg[1].trim();
+                check_cfg_predicate(cfg_pred)?;
+                stmt_header.add_cfg(cfg_pred);
+
+            } else if let Some( order ) = STMT_ORDER.captures( comment ) {
+                stmt_header.parse_order(&order[1])?;
+
+            } else if let Some( tables ) = STMT_TABLES.captures( comment ) {
+                stmt_header.parse_tables(&tables[1])?;
+
             } else if let Some( param ) = STMT_PARAM.captures( comment ) {
                 let param_name = to_snake_case(&param[1]);
-                let param_type = param[2].to_string();
+                let param_mode = ParamMode::from_keyword(param.get(2).map(|mode_match| mode_match.as_str()));
+                let param_type = param[3].to_string();
+                let (param_default, param_docs) = split_default(&param[4]);
                 // build a doc-comment line for this parameter
-                if !stmt_docs.is_empty() {
-                    stmt_docs.push('\n');
+                let mut doc_line = String::with_capacity(param_name.len() + param_docs.len() + 5);
+                doc_line.push_str(" * `");
+                doc_line.push_str(param_name.as_str());
+                doc_line.push_str("` ");
+                doc_line.push_str(param_docs);
+
+                let param_idents = match IDENT_TYPE.captures(&param_type) {
+                    Some(idents) => parse_choices(&idents[1], &IDENT_NAME, "identifier", &param_name)?,
+                    None => Vec::new()
+                };
+                let param_values = match VALUE_TYPE.captures(&param_type) {
+                    Some(values) => parse_choices(&values[1], &VALUE_NAME, "value", &param_name)?,
+                    None => Vec::new()
+                };
+                // allow-lists and enumerated values are represented by enums that are named after their parameters
+                let param_type = if param_idents.is_empty() && param_values.is_empty() { type_aliases.expand(&param_type)? } else { param_name.to_camel_case() };
+                let mut stmt_param = StmtParam::new(param_name, param_type);
+                stmt_param.mode = param_mode;
+                stmt_param.default = param_default;
+                stmt_param.idents = param_idents;
+                stmt_param.values = param_values;
+                param_continues = true;
+                if file_header {
+                    // parameters that are declared in the file header are shared by all statements
+                    if sql_text.shared_params.iter().any(|(shared_param, _)| shared_param.name == stmt_param.name) {
+                        return Err(err::new(format!("shared param `{}` is declared more than once", &stmt_param.name)));
+                    }
+                    sql_text.shared_params.push((stmt_param, doc_line));
+                } else {
+                    stmt_header.push_doc_line(&doc_line);
+                    stmt_header.params_docs_end = Some(stmt_header.docs.len());
+                    stmt_header.params.push(stmt_param);
                 }
-                stmt_docs.push_str(" * `");
-                stmt_docs.push_str(param_name.as_str());
-                stmt_docs.push_str("` ");
-                stmt_docs.push_str(&param[3]);
 
-                stmt_params.push(StmtParam::new(param_name, param_type));
+            } else if let Some( attr ) = STMT_ATTR.captures( comment ) {
+                let attr_name = attr.get(1).or(attr.get(3)).map(|name_match| name_match.as_str()).unwrap_or_default();
+                let attr_value = attr.get(2).or(attr.get(4)).map(|value_match| value_match.as_str().trim()).unwrap_or_default();
+                stmt_header.attrs.push(StmtAttr::new(attr_name.to_string(), attr_value.to_string()));
+
+            } else if let Some( attr ) = RUST_ATTR.captures( comment ) {
+                let rust_attr = &attr[1];
+                if let Err(parse_err) = syn::Attribute::parse_outer.parse_str(rust_attr) {
+                    return Err(err::new(format!("`{}` is not a valid Rust attribute: {}", rust_attr, parse_err)));
+                }
+                stmt_header.rust_attrs.push(rust_attr.to_string());
+
             } else {
+                if file_header {
+                    // The leading comment block of the file documents the file itself
+                    if !file_docs.is_empty() {
+                        file_docs.push('\n');
+                    }
+                    file_docs.push_str(comment);
+                }
                 // A comment or a doc-comment line.
                 // It depends on whether this statement's name: has been parsed already
-                if !stmt_docs.is_empty() {
-                    stmt_docs.push('\n');
-                }
-                stmt_docs.push_str(comment);
+                stmt_header.push_doc_line(comment);
             }
 
         } else {
+            file_header = false;
+            param_continues = false;
             if !stmt_text.is_empty() {
                 stmt_text.push('\n');
             }
@@ -93,27 +276,149 @@ fn parse_text(text: &str) -> Vec<Stmt> {
                 // statement is explicitly terminated
                 stmt_text.push_str(last_line.trim_end());
                 if !stmt_text.is_empty() {
-                    let stmt = Stmt::new(stmt_name, stmt_into, stmt_params, &stmt_text, &stmt_docs);
-                    stmt_list.push(stmt);
-
+                    sql_text.push(std::mem::take(&mut stmt_header), &stmt_text)?;
                     stmt_text.clear();
-                    stmt_params = Vec::new();
                 } else {
-                    stmt_params.clear();
+                    stmt_header = StmtHeader::default();
                 }
-                stmt_name = None;
-                stmt_into = None;
-                stmt_docs.clear();
             } else {
-                stmt_text.push_str(&line);
+                stmt_text.push_str(line);
             }
         }
     }
     if !stmt_text.is_empty() {
-        let stmt = Stmt::new(stmt_name, stmt_into, stmt_params, &stmt_text, &stmt_docs);
-        stmt_list.push(stmt);
+        sql_text.push(stmt_header, &stmt_text)?;
+    }
+    sql_text.docs = if file_docs.is_empty() { None } else { Some(file_docs) };
+    Ok(sql_text)
+}
+
+/// Checks whether the comment is a meta comment, which thus does not continue a parameter description.
+fn is_meta_comment(comment: &str) -> bool {
+    [
+        &STMT_NAME, &STMT_PARAM, &STMT_COLUMNS, &STMT_ORDER, &STMT_TABLES, &STMT_FRAGMENT, &TYPE_ALIAS, &IMPORT_LINE,
+        &INCLUDE_LINE, &RUST_ATTR, &STMT_CFG, &CFG_IF, &CFG_ENDIF, &OPT_IF, &OPT_END, &STMT_ATTR,
+    ].iter().any(|pattern| pattern.is_match(comment))
+}
+
+/// Statements and fragments of the SQL file before fragment references are expanded
+#[derive(Debug, Default)]
+pub(crate) struct SqlText {
+    docs: Option<String>,
+    imports: Vec<String>,
+    /// Number of leading `stmts` that were imported from other files
+    num_imported: usize,
+    /// Parameters that are declared in the file header and their doc-comment lines
+    shared_params: Vec<(StmtParam, String)>,
+    stmts: Vec<(StmtHeader, String)>,
+    fragments: Vec<(StmtHeader, String)>,
+}
+
+impl SqlText {
+    /// Returns paths of the files this file imports as they were written in `import:` lines.
+    pub(crate) fn imports(&self) -> &[String] {
+        &self.imports
+    }
+
+    /// Merges statements and fragments of the imported file into this text.
+    ///
+    /// Imported statements precede the statements of this file in the order of imports.
+    pub(crate) fn import(&mut self, imported: SqlText) {
+        let num_imported = imported.stmts.len();
+        self.stmts.splice(self.num_imported..self.num_imported, imported.stmts);
+        self.num_imported += num_imported;
+        self.fragments.extend(imported.fragments);
+    }
+
+    /// Expands fragments and builds the statement list of the included file.
+    pub(crate) fn into_included_sql(self, file_name: &str) -> Result<IncludedSql> {
+        let file_name = file_name.to_string();
+        let docs = self.docs.clone();
+        let stmt_list = self.into_stmt_list()?;
+        check_stmt_names(&stmt_list)?;
+        let stmt_list = group_variants(stmt_list)?;
+        check_parameters(&stmt_list)?;
+        check_ident_params(&stmt_list)?;
+        let mut included_sql = IncludedSql { file_name, docs, stmt_list };
+        included_sql.prefix_enum_names();
+        Ok(included_sql)
+    }
+
+    fn push(&mut self, mut header: StmtHeader, text: &str) -> Result<()> {
+        header.check_groups()?;
+        if header.is_fragment {
+            self.fragments.push((header, text.trim_end().to_string()));
+        } else {
+            header.shared_params = self.shared_params.clone();
+            self.stmts.push((header, text.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Expands fragment references and creates statements.
+    fn into_stmt_list(mut self) -> Result<Vec<Stmt>> {
+        for (i, (fragment, _)) in self.fragments.iter().enumerate() {
+            if self.fragments[..i].iter().any(|(other, _)| other.name == fragment.name) {
+                return Err(err::new(format!("fragment `{}` is defined more than once", fragment.name.as_deref().unwrap_or_default())));
+            }
+        }
+        let stmts = std::mem::take(&mut self.stmts);
+        let mut stmt_list = Vec::with_capacity(stmts.len());
+        for (mut header, text) in stmts {
+            let groups = std::mem::take(&mut header.groups);
+            let (text, groups) = self.expand_fragments(&text, groups, &mut Vec::new())?;
+            header.groups = groups;
+            stmt_list.push(Stmt::new(header, &text)?);
+        }
+        Ok(stmt_list)
+    }
+
+    /// Replaces fragment references in the text with the fragment text.
+    ///
+    /// Returns the expanded text and the list of conditional fragments of the expanded text.
+    fn expand_fragments(&self, text: &str, groups: Vec<StmtGroup>, included: &mut Vec<String>) -> Result<(String, Vec<StmtGroup>)> {
+        if !FRAGMENT_REF.is_match(text) {
+            return Ok((text.to_string(), groups));
+        }
+        let mut expanded_text = String::with_capacity(text.len() * 2);
+        let mut expanded_groups = Vec::with_capacity(groups.len());
+        let mut groups = groups.into_iter();
+        let mut text_start = 0;
+        for caps in FRAGMENT_REF.captures_iter(text) {
+            let fragment_ref = caps.get(0).map(|ref_match| ref_match.range()).unwrap_or_default();
+            let text_before = &text[text_start..fragment_ref.start];
+            expanded_text.push_str(text_before);
+            // keep conditional fragments in the order they start in the expanded text
+            for _ in text_before.matches(GROUP_START) {
+                expanded_groups.extend(groups.next());
+            }
+            let name = &caps[1];
+            if included.iter().any(|included_name| included_name == name) {
+                included.push(name.to_string());
+                return Err(err::new(format!("fragment `{}` includes itself via `{}`", name, included.join("` -> `"))));
+            }
+            let (fragment, fragment_text) = self.fragments.iter()
+                .find(|(fragment, _)| fragment.name.as_deref() == Some(name))
+                .ok_or_else(|| err::new(format!("fragment `{}` is not defined", name)))?;
+            included.push(name.to_string());
+            let (fragment_text, fragment_groups) = self.expand_fragments(fragment_text, fragment.groups.clone(), included)?;
+            included.pop();
+            expanded_text.push_str(&fragment_text);
+            expanded_groups.extend(fragment_groups);
+            text_start = fragment_ref.end;
+        }
+        expanded_text.push_str(&text[text_start..]);
+        expanded_groups.extend(groups);
+        Ok((expanded_text, expanded_groups))
+    }
+}
+
+/// Checks that the text is a valid `cfg` predicate.
+fn check_cfg_predicate(pred: &str) -> Result<()> {
+    match syn::parse_str::<syn::Meta>(pred) {
+        Ok(_) => Ok(()),
+        Err(parse_err) => Err(err::new(format!("`{}` is not a valid cfg predicate: {}", pred, parse_err)))
     }
-    stmt_list
 }
 
 fn check_stmt_names(stmt_list: &[Stmt]) -> Result<()> {
@@ -122,8 +427,13 @@ fn check_stmt_names(stmt_list: &[Stmt]) -> Result<()> {
             if stmt.name.is_empty() {
                 let text = match &stmt.items[0] {
                     StmtItem::Text(text) => String::from(text),
-                    StmtItem::Bind(name) => String::from(":") + &name,
-                    StmtItem::List(name) => String::from(":") + &name,
+                    StmtItem::Bind(name) => String::from(":") + name,
+                    StmtItem::List(name) => String::from(":") + name,
+                    StmtItem::Tuples(name, _) => String::from(":") + name,
+                    StmtItem::Ident(name) => String::from(":!") + name,
+                    StmtItem::Field(name, path) => String::from(":") + name + "." + &path.join("."),
+                    StmtItem::NullSafe(name, _) => String::from(":") + name,
+                    StmtItem::Cfg(..) | StmtItem::Opt(..) => String::new(),
                 };
                 return Err(err::new(format!("statement `{}...` must have a name", text)));
             }
@@ -135,12 +445,80 @@ fn check_stmt_names(stmt_list: &[Stmt]) -> Result<()> {
     Ok(())
 }
 
+/// Places dialect variants of a statement next to each other, and checks that
+/// each statement is defined only once for each dialect.
+fn group_variants(stmt_list: Vec<Stmt>) -> Result<Vec<Stmt>> {
+    let mut grouped_list : Vec<Stmt> = Vec::with_capacity(stmt_list.len());
+    for stmt in stmt_list {
+        let same_name = grouped_list.iter().rposition(|variant| variant.name == stmt.name);
+        if let Some(pos) = same_name {
+            let is_duplicate = grouped_list[..=pos].iter().any(|variant| variant.name == stmt.name && variant.dialect == stmt.dialect);
+            if is_duplicate {
+                return Err(match stmt.dialect.as_ref() {
+                    Some(dialect) => err::new(format!("statement `{}` is defined more than once for `{}`", &stmt.name, dialect)),
+                    None => err::new(format!("statement `{}` is defined more than once", &stmt.name)),
+                });
+            }
+            grouped_list.insert(pos + 1, stmt);
+        } else {
+            grouped_list.push(stmt);
+        }
+    }
+    Ok(grouped_list)
+}
+
 fn check_parameters(stmt_list: &[Stmt]) -> Result<()> {
     for stmt in stmt_list {
         for param in &stmt.params {
             if !stmt.items.iter().any(|item| item.is_bind(&param.name)) {
                 return Err(err::new(format!("param `{}` is not found in `{}`", &param.name, &stmt.name)))
             }
+            if param.mode != ParamMode::In && stmt.unique_binds().iter().any(|item| matches!(item, StmtItem::List(name) if name == &param.name)) {
+                return Err(err::new(format!("IN list param `{}` cannot be an output parameter in `{}`", &param.name, &stmt.name)))
+            }
+            if param.mode != ParamMode::In && stmt.unique_binds().iter().any(|item| matches!(item, StmtItem::Tuples(name, _) if name == &param.name)) {
+                return Err(err::new(format!("tuple list param `{}` cannot be an output parameter in `{}`", &param.name, &stmt.name)))
+            }
+            if param.mode != ParamMode::In && stmt.unique_binds().iter().any(|item| matches!(item, StmtItem::Field(name, _) if name == &param.name)) {
+                return Err(err::new(format!("struct param `{}` cannot be an output parameter in `{}`", &param.name, &stmt.name)))
+            }
+            if !param.idents.is_empty() && param.mode != ParamMode::In {
+                return Err(err::new(format!("identifier param `{}` cannot be an output parameter in `{}`", &param.name, &stmt.name)))
+            }
+            if !param.values.is_empty() && param.mode != ParamMode::In {
+                return Err(err::new(format!("enum param `{}` cannot be an output parameter in `{}`", &param.name, &stmt.name)))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that identifier placeholders have allow-lists, that parameters with allow-lists are used
+/// only as identifier placeholders, and that parameters with the same name have the same allow-list
+/// or list of enumerated values, as each list becomes a type that is named after its parameter.
+fn check_ident_params(stmt_list: &[Stmt]) -> Result<()> {
+    for stmt in stmt_list {
+        for bind in stmt.unique_binds() {
+            let param = stmt.params.iter().find(|param| bind.is_bind(&param.name));
+            match bind {
+                StmtItem::Ident(name) if param.is_none_or(|param| param.idents.is_empty()) => {
+                    return Err(err::new(format!("identifier param `{}` must be declared with `ident(...)` allow-list in `{}`", name, &stmt.name)));
+                },
+                StmtItem::Ident(_) => {},
+                _ => if let Some(param) = param.filter(|param| !param.idents.is_empty()) {
+                    return Err(err::new(format!("param `{}` has an allow-list of identifiers and must be used as `:!{}` in `{}`", &param.name, &param.name, &stmt.name)));
+                }
+            }
+        }
+    }
+    let mut enum_params : Vec<&StmtParam> = Vec::new();
+    for param in stmt_list.iter().flat_map(|stmt| stmt.params.iter()).filter(|param| param.is_enum()) {
+        match enum_params.iter().find(|other| other.rust_type == param.rust_type) {
+            Some(other) if other.idents != param.idents || other.values != param.values => {
+                return Err(err::new(format!("param `{}` is declared with different lists of identifiers or values", &param.name)));
+            },
+            Some(_) => {},
+            None => enum_params.push(param)
         }
     }
     Ok(())
@@ -150,6 +528,8 @@ fn check_parameters(stmt_list: &[Stmt]) -> Result<()> {
 #[derive(Debug)]
 pub(crate) struct IncludedSql {
     pub(crate) file_name: String,
+    /// The leading comment block of the file.
+    pub(crate) docs: Option<String>,
     pub(crate) stmt_list: Vec<Stmt>
 }
 
@@ -161,16 +541,135 @@ pub(crate) struct Stmt {
     /// For example, ? might indicate that the generated macro would
     /// retrieve and process rows returned by a query, etc.
     pub(crate) into: String,
+    /// Whether the variant selector was omitted in the statement header
+    pub(crate) into_omitted: bool,
+    /// Variant selector that is suggested by the statement text
+    pub(crate) inferred_into: Option<&'static str>,
+    /// Number of rows the statement is expected to return or affect
+    pub(crate) cardinality: Option<Cardinality>,
     pub(crate) docs: Option<String>,
     pub(crate) params: Vec<StmtParam>,
+    /// What kind of statement this is
+    pub(crate) class: StmtClass,
+    /// Whether the statement only reads data
+    pub(crate) read_only: bool,
+    /// Tables that the statement reads
+    pub(crate) reads: Vec<String>,
+    /// Tables that the statement modifies
+    pub(crate) writes: Vec<String>,
+    /// Declared columns of the rows the statement returns
+    pub(crate) columns: Vec<StmtColumn>,
+    /// Back-end specific statement attributes
+    pub(crate) attrs: Vec<StmtAttr>,
+    /// Rust attributes for the generated method
+    pub(crate) rust_attrs: Vec<String>,
+    /// Predicate of the `cfg` attribute that gates the statement
+    pub(crate) cfg: Option<String>,
+    /// SQL dialect of this statement variant
+    pub(crate) dialect: Option<String>,
+    /// Parameters whose placeholders are marked as nullable
+    pub(crate) nullable: Vec<String>,
+    /// Parameter order that is declared by the `params:` line
+    pub(crate) order: Vec<String>,
     pub(crate) items: Vec<StmtItem>,
 }
 
+/// Statement properties that are collected from its doc-comment
+#[derive(Debug, Default)]
+struct StmtHeader {
+    name: Option<String>,
+    into: Option<String>,
+    cardinality: Option<Cardinality>,
+    docs: String,
+    /// End of the doc-comment lines of the last declared parameter
+    params_docs_end: Option<usize>,
+    params: Vec<StmtParam>,
+    columns: Vec<StmtColumn>,
+    /// Tables that the statement reads as declared by the `tables:` line
+    reads: Option<Vec<String>>,
+    /// Tables that the statement writes as declared by the `tables:` line
+    writes: Option<Vec<String>>,
+    attrs: Vec<StmtAttr>,
+    rust_attrs: Vec<String>,
+    cfg: Option<String>,
+    dialect: Option<String>,
+    /// Conditional fragments of the statement text in the order they start
+    groups: Vec<StmtGroup>,
+    /// Indexes of conditional fragments that have not been closed yet
+    open_groups: Vec<usize>,
+    /// Whether this is a header of a reusable fragment rather than of a statement
+    is_fragment: bool,
+    /// Parameters that are declared in the file header and their doc-comment lines
+    shared_params: Vec<(StmtParam, String)>,
+    /// Parameter order as declared by the `params:` line
+    order: Vec<String>,
+}
+
+/// Represents a condition of a conditional fragment of the statement text
+#[derive(Debug, Clone)]
+enum StmtGroup {
+    /// Fragment is included if the `cfg` predicate is true
+    Cfg(String),
+    /// Fragment is included if the parameter has a value
+    Opt(String),
+}
+
 /// Represents a declared statement parameter
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct StmtParam {
     pub(crate) name: String,
     pub(crate) rust_type: String,
+    /// Direction in which the parameter passes its value
+    pub(crate) mode: ParamMode,
+    /// Rust expression that provides the parameter value when it is not specified
+    pub(crate) default: Option<String>,
+    /// Identifiers that an identifier parameter can be substituted with
+    pub(crate) idents: Vec<String>,
+    /// Values that an enumerated parameter can have
+    pub(crate) values: Vec<String>,
+}
+
+/// Direction of a stored procedure parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum ParamMode {
+    /// The value is passed to the database
+    #[default]
+    In,
+    /// The value is returned by the database
+    Out,
+    /// The value is passed to the database and then returned
+    InOut,
+}
+
+/// Number of rows a statement returns or affects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cardinality {
+    /// Exactly one row
+    One,
+    /// Zero or one row
+    Optional,
+    /// Any number of rows
+    Many,
+    /// Statement does not return rows
+    Exec,
+    /// Statement returns the number of affected rows
+    RowsAffected,
+}
+
+/// Represents a declared result column
+#[derive(Debug)]
+pub(crate) struct StmtColumn {
+    pub(crate) name: String,
+    pub(crate) rust_type: String,
+}
+
+/// Represents a back-end specific statement attribute
+#[derive(Debug)]
+pub(crate) struct StmtAttr {
+    pub(crate) name: String,
+    /// Attribute value as it is written in SQL.
+    /// Empty if the attribute is declared without a value.
+    pub(crate) value: String,
 }
 
 pub(crate) trait SelectStmtParamType {
@@ -178,7 +677,7 @@ pub(crate) trait SelectStmtParamType {
 }
 
 /// Represents an element of the SQL statement
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum StmtItem {
     /// Text portion of the SQL statement
     Text(String),
@@ -186,6 +685,19 @@ pub(crate) enum StmtItem {
     Bind(String),
     /// IN-list parameter placeholder
     List(String),
+    /// Tuple list parameter placeholder and the names of the tuple fields.
+    /// Fields of tuple types are named by their indexes.
+    Tuples(String, Vec<String>),
+    /// Struct field placeholder - the parameter name and the path to the field
+    Field(String, Vec<String>),
+    /// Identifier placeholder that is substituted with one of the allowed identifiers
+    Ident(String),
+    /// Null-safe comparison with a nullable parameter, and the comparison operator as it is written in SQL
+    NullSafe(String, String),
+    /// Fragment that is included if the `cfg` predicate is true
+    Cfg(String, Vec<StmtItem>),
+    /// Fragment that is included if the parameter has a value
+    Opt(String, Vec<StmtItem>),
 }
 
 pub(crate) trait TakeStmtItem {
@@ -193,15 +705,101 @@ pub(crate) trait TakeStmtItem {
 }
 
 impl Stmt {
-    fn new(name: Option<String>, into: Option<String>, params: Vec<StmtParam>, stmt_text: &str, stmt_docs: &str) -> Self {
+    fn new(header: StmtHeader, stmt_text: &str) -> Result<Self> {
+        let StmtHeader { name, into, cardinality, mut docs, params_docs_end, mut params, columns, reads, writes, attrs, rust_attrs, cfg, dialect, groups, shared_params, order, .. } = header;
         let name = name.unwrap_or_default();
-        let into = into.unwrap_or_else(|| "!".to_string());
-        let items = Self::parse_text(stmt_text);
-        let docs = if stmt_docs.is_empty() { None } else { Some(stmt_docs.to_string()) };
-        Self { name, params, into, docs, items }
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|other| other.name == column.name) {
+                return Err(err::new(format!("result column `{}` is declared more than once in `{}`", column.name, name)));
+            }
+        }
+        let into_omitted = into.is_none();
+        let into = into.unwrap_or_else(|| cardinality.map(Cardinality::default_into).unwrap_or("!").to_string());
+        let tokens = scan::tokenize(stmt_text);
+        let class = scan::classify(&tokens);
+        let read_only = scan::is_read_only(class, &tokens);
+        let inferred_into = scan::infer_variant(class, &tokens);
+        let (found_reads, found_writes) = scan::referenced_tables(class, &tokens);
+        let reads = reads.unwrap_or(found_reads);
+        let writes = writes.unwrap_or(found_writes);
+        let mut nullable = Vec::new();
+        let items = Self::parse_text(stmt_text, &mut nullable, &name)?;
+        let items = if groups.is_empty() { items } else { Self::group_items(items, groups) };
+        // doc-comment lines of shared parameters follow those of the statement parameters
+        let mut shared_docs_pos = params_docs_end.unwrap_or(docs.len());
+        for (param, mut doc_line) in shared_params {
+            // statement parameter declarations override the shared ones
+            if params.iter().all(|stmt_param| stmt_param.name != param.name) && items.iter().any(|item| item.is_bind(&param.name)) {
+                if shared_docs_pos > 0 {
+                    doc_line.insert(0, '\n');
+                }
+                docs.insert_str(shared_docs_pos, &doc_line);
+                shared_docs_pos += doc_line.len();
+                params.push(param);
+            }
+        }
+        let items = Self::resolve_tuples(items, &params, &name)?;
+        // `Option` parameters of optional fragments are `None` when the fragment is omitted rather than NULL
+        let nullable_params = params.iter()
+            .filter(|param| syn::parse_str::<syn::Type>(&param.rust_type).is_ok_and(|param_type| is_option(&param_type)))
+            .filter(|param| !matches!(Self::find_bind(&items, &param.name, false), (false, true)))
+            .map(|param| param.name.clone())
+            .chain(nullable.iter().cloned())
+            .collect::<Vec<_>>();
+        let items = if nullable_params.is_empty() { items } else { Self::mark_null_safe(items, &nullable_params, &mut String::new()) };
+        Self::check_optional_fragments(&items, &name)?;
+        Self::check_struct_params(&items, &params, &name)?;
+        Self::check_order(&items, &order, &name)?;
+        let docs = if docs.is_empty() { None } else { Some(docs) };
+        Ok(Self { name, params, columns, into, into_omitted, inferred_into, cardinality, class, read_only, reads, writes, docs, attrs, rust_attrs, cfg, dialect, nullable, order, items })
     }
 
-    fn parse_text(text: &str) -> Vec<StmtItem> {
+    /// Nests items that are found between conditional fragment markers into their groups.
+    fn group_items(items: Vec<StmtItem>, groups: Vec<StmtGroup>) -> Vec<StmtItem> {
+        let mut groups = groups.into_iter();
+        let mut stack : Vec<(Option<StmtGroup>, Vec<StmtItem>)> = vec![(None, Vec::new())];
+        for item in items {
+            let StmtItem::Text(text) = item else {
+                if let Some((_, group_items)) = stack.last_mut() {
+                    group_items.push(item);
+                }
+                continue;
+            };
+            let mut text = text.as_str();
+            while !text.is_empty() {
+                let marker_pos = text.find([GROUP_START, GROUP_END]).unwrap_or(text.len());
+                if marker_pos > 0 {
+                    if let Some((_, group_items)) = stack.last_mut() {
+                        group_items.push(StmtItem::Text(text[..marker_pos].to_string()));
+                    }
+                }
+                let mut rest = text[marker_pos..].chars();
+                match rest.next() {
+                    Some(GROUP_START) => {
+                        stack.push((groups.next(), Vec::new()));
+                    },
+                    Some(GROUP_END) if stack.len() > 1 => {
+                        let group_item = match stack.pop() {
+                            Some((Some(StmtGroup::Cfg(pred)), group_items)) => Some(StmtItem::Cfg(pred, group_items)),
+                            Some((Some(StmtGroup::Opt(param)), group_items)) => Some(StmtItem::Opt(param, group_items)),
+                            _ => None
+                        };
+                        if let (Some(group_item), Some((_, parent_items))) = (group_item, stack.last_mut()) {
+                            parent_items.push(group_item);
+                        }
+                    },
+                    _ => {}
+                }
+                text = rest.as_str();
+            }
+        }
+        stack.pop().map(|(_, items)| items).unwrap_or_default()
+    }
+
+    /// Splits the statement text into text fragments and parameter placeholders.
+    ///
+    /// Names of parameters whose placeholders are marked as nullable - `:name?` - are added to `nullable`.
+    fn parse_text(text: &str, nullable: &mut Vec<String>, stmt_name: &str) -> Result<Vec<StmtItem>> {
         let mut items = Vec::new();
         let mut text_start = 0;
         for caps in BIND_NAME.captures_iter(text) {
@@ -213,136 +811,776 @@ impl Stmt {
             } else if let Some( bind ) = caps.get(2) {
                 let bind_range = bind.range();
                 items.push(StmtItem::Text(text[text_start..bind_range.start].to_string()));
-                items.push(StmtItem::Bind(text[(bind_range.start + 1)..bind_range.end].to_snake_case()));
                 text_start = bind_range.end;
+                if let Some( ident ) = text[(bind_range.start + 1)..bind_range.end].strip_prefix('!') {
+                    if caps.get(3).is_some() {
+                        return Err(err::new(format!("identifier placeholder `:!{}` cannot be marked as nullable in `{}`", ident, stmt_name)));
+                    }
+                    items.push(StmtItem::Ident(ident.to_snake_case()));
+                    continue;
+                }
+                let name = text[(bind_range.start + 1)..bind_range.end].to_snake_case();
+                if let Some( marker ) = caps.get(3) {
+                    if !nullable.contains(&name) {
+                        nullable.push(name.clone());
+                    }
+                    text_start = marker.end();
+                } else if let Some( path ) = FIELD_PATH.find(&text[text_start..]) {
+                    if text[text_start + path.end()..].starts_with('?') {
+                        return Err(err::new(format!("struct field placeholder `:{}{}` cannot be marked as nullable in `{}`", name, path.as_str(), stmt_name)));
+                    }
+                    let field_path = path.as_str()[1..].split('.').map(str::to_snake_case).collect();
+                    text_start += path.end();
+                    items.push(StmtItem::Field(name, field_path));
+                    continue;
+                } else if let Some( fields ) = TUPLE_FIELDS.captures(&text[text_start..]) {
+                    let fields_range = fields.get(0).map(|fields_match| fields_match.range()).unwrap_or_default();
+                    let field_names = fields[1].split(',').map(|field| field.trim().to_snake_case()).collect();
+                    text_start += fields_range.end;
+                    items.push(StmtItem::Tuples(name, field_names));
+                    continue;
+                }
+                items.push(StmtItem::Bind(name));
             }
         }
         let stmt_tail = text[text_start..].trim_end();
         if !stmt_tail.is_empty() {
             items.push(StmtItem::Text(stmt_tail.to_string()));
         }
-        items
+        Ok(items)
+    }
+
+    /// Turns IN lists of parameters with tuple types into tuple lists, and names fields
+    /// of tuple lists with tuple types by their indexes.
+    fn resolve_tuples(items: Vec<StmtItem>, params: &[StmtParam], stmt_name: &str) -> Result<Vec<StmtItem>> {
+        let mut resolved = Vec::with_capacity(items.len());
+        for item in items {
+            let item = match item {
+                StmtItem::List(name) => match tuple_arity(params, &name) {
+                    Some(arity) => StmtItem::Tuples(name, (0..arity).map(|i| i.to_string()).collect()),
+                    None => StmtItem::List(name)
+                },
+                StmtItem::Tuples(name, fields) => match tuple_arity(params, &name) {
+                    Some(arity) if arity != fields.len() => {
+                        return Err(err::new(format!("tuple list param `{}` has {} fields, but its type has {} elements in `{}`", name, fields.len(), arity, stmt_name)));
+                    },
+                    Some(arity) => StmtItem::Tuples(name, (0..arity).map(|i| i.to_string()).collect()),
+                    None => StmtItem::Tuples(name, fields)
+                },
+                StmtItem::Cfg(pred, group_items) => StmtItem::Cfg(pred, Self::resolve_tuples(group_items, params, stmt_name)?),
+                StmtItem::Opt(param, group_items) => StmtItem::Opt(param, Self::resolve_tuples(group_items, params, stmt_name)?),
+                _ => item
+            };
+            resolved.push(item);
+        }
+        Ok(resolved)
+    }
+
+    /// Replaces equality comparisons with nullable parameters by null-safe comparisons.
+    ///
+    /// `preceding` accumulates the statement text before the item, which is used to tell
+    /// comparisons from `SET` assignments.
+    fn mark_null_safe(items: Vec<StmtItem>, nullable: &[String], preceding: &mut String) -> Vec<StmtItem> {
+        let mut marked = Vec::with_capacity(items.len());
+        for item in items {
+            match item {
+                StmtItem::Text(text) => {
+                    preceding.push_str(&text);
+                    marked.push(StmtItem::Text(text));
+                },
+                StmtItem::Bind(name) if nullable.contains(&name) => {
+                    let op = match marked.last_mut() {
+                        Some(StmtItem::Text(text)) if !is_assignment(preceding) => take_comparison(text),
+                        _ => None
+                    };
+                    if let Some(op) = op {
+                        if matches!(marked.last(), Some(StmtItem::Text(text)) if text.is_empty()) {
+                            marked.pop();
+                        }
+                        marked.push(StmtItem::NullSafe(name, op));
+                    } else {
+                        marked.push(StmtItem::Bind(name));
+                    }
+                },
+                StmtItem::Cfg(pred, group_items) => {
+                    marked.push(StmtItem::Cfg(pred, Self::mark_null_safe(group_items, nullable, preceding)));
+                },
+                StmtItem::Opt(param, group_items) => {
+                    marked.push(StmtItem::Opt(param, Self::mark_null_safe(group_items, nullable, preceding)));
+                },
+                _ => marked.push(item),
+            }
+        }
+        marked
+    }
+
+    /// Checks that parameters of optional fragments are used in those fragments.
+    fn check_optional_fragments(items: &[StmtItem], stmt_name: &str) -> Result<()> {
+        for item in items {
+            match item {
+                StmtItem::Opt(param, group_items) => {
+                    if !group_items.iter().any(|item| item.is_bind(param)) {
+                        return Err(err::new(format!("optional fragment parameter `{}` is not used in its fragment in `{}`", param, stmt_name)));
+                    }
+                    Self::check_optional_fragments(group_items, stmt_name)?;
+                },
+                StmtItem::Cfg(_, group_items) => {
+                    Self::check_optional_fragments(group_items, stmt_name)?;
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that struct parameters are declared and that their placeholders are all field placeholders.
+    fn check_struct_params(items: &[StmtItem], params: &[StmtParam], stmt_name: &str) -> Result<()> {
+        let mut binds = Vec::with_capacity(items.len());
+        Self::collect_binds(items, &mut binds);
+        for bind in &binds {
+            let StmtItem::Field(name, _) = bind else { continue; };
+            if !params.iter().any(|param| &param.name == name) {
+                return Err(err::new(format!("struct param `{}` must be declared by `param:` in `{}`", name, stmt_name)));
+            }
+            if binds.iter().any(|other| other.is_bind(name) && !matches!(other, StmtItem::Field(..))) {
+                return Err(err::new(format!("struct param `{}` is also used as a value in `{}`", name, stmt_name)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the `params:` line, if there is one, lists all statement parameters and only them,
+    /// so that parameters cannot be reordered or added without changing it.
+    fn check_order(items: &[StmtItem], order: &[String], stmt_name: &str) -> Result<()> {
+        if order.is_empty() {
+            return Ok(());
+        }
+        if let Some(name) = order.iter().find(|name| !items.iter().any(|item| item.is_bind(name))) {
+            return Err(err::new(format!("param `{}` listed in `params:` is not found in `{}`", name, stmt_name)));
+        }
+        let mut binds = Vec::with_capacity(items.len());
+        Self::collect_binds(items, &mut binds);
+        if let Some(name) = binds.iter().filter_map(|bind| bind.param_name()).find(|name| !order.iter().any(|listed| listed == name)) {
+            return Err(err::new(format!("param `{}` is not listed in `params:` of `{}`", name, stmt_name)));
+        }
+        Ok(())
+    }
+
+    /// Collects all parameter placeholders including those in conditional fragments.
+    fn collect_binds<'a>(items: &'a [StmtItem], binds: &mut Vec<&'a StmtItem>) {
+        for item in items {
+            match item {
+                StmtItem::Text(_) => {},
+                StmtItem::Cfg(_, group_items) | StmtItem::Opt(_, group_items) => Self::collect_binds(group_items, binds),
+                _ => binds.push(item),
+            }
+        }
+    }
+
+    /// Checks whether the parameter is used only inside optional fragments.
+    pub(crate) fn is_optional(&self, name: &str) -> bool {
+        let (required, optional) = Self::find_bind(&self.items, name, false);
+        optional && !required
+    }
+
+    /// Checks whether the parameter placeholder is marked as nullable.
+    pub(crate) fn is_nullable(&self, name: &str) -> bool {
+        self.nullable.iter().any(|param_name| param_name == name)
+    }
+
+    /// Returns whether the parameter is used outside and inside optional fragments.
+    fn find_bind(items: &[StmtItem], name: &str, in_optional: bool) -> (bool, bool) {
+        let mut found = (false, false);
+        for item in items {
+            let (required, optional) = match item {
+                StmtItem::Cfg(_, group_items) => Self::find_bind(group_items, name, in_optional),
+                StmtItem::Opt(_, group_items) => Self::find_bind(group_items, name, true),
+                _ if item.is_bind(name) => (!in_optional, in_optional),
+                _ => (false, false)
+            };
+            found = (found.0 || required, found.1 || optional);
+        }
+        found
     }
 
     pub(crate) fn unique_binds(&self) -> Vec<&StmtItem> {
         let mut binds = Vec::with_capacity(self.items.len());
         let mut names = Vec::with_capacity(self.items.len());
-        for item in &self.items {
+        Self::collect_unique_binds(&self.items, &mut binds, &mut names);
+        binds
+    }
+
+    fn collect_unique_binds<'a>(items: &'a [StmtItem], binds: &mut Vec<&'a StmtItem>, names: &mut Vec<&'a String>) {
+        for item in items {
             match item {
                 StmtItem::Bind(name) if !names.contains(&name) => {
                     names.push(name);
                     binds.push(item)
                 },
-                StmtItem::List(name) if !names.contains(&name) => {
+                StmtItem::List(name) | StmtItem::Tuples(name, _) | StmtItem::Field(name, _) | StmtItem::Ident(name) | StmtItem::NullSafe(name, _) if !names.contains(&name) => {
                     names.push(name);
                     binds.push(item)
                 },
+                StmtItem::Cfg(_, group_items) | StmtItem::Opt(_, group_items) => {
+                    Self::collect_unique_binds(group_items, binds, names);
+                },
                 _ => {}
             }
         }
-        binds
     }
 }
 
-impl StmtParam {
-    fn new(name: String, rust_type: String) -> Self {
-        Self { name, rust_type }
-    }
+/// Removes the trailing equality or inequality operator from the text.
+///
+/// Returns the removed operator with the whitespace that follows it.
+fn take_comparison(text: &mut String) -> Option<String> {
+    let trimmed = text.trim_end();
+    let op_len = if trimmed.ends_with("<>") || trimmed.ends_with("!=") {
+        2
+    } else if trimmed.ends_with('=') && !trimmed[..trimmed.len() - 1].ends_with(['<', '>', '!', ':']) {
+        1
+    } else {
+        return None;
+    };
+    Some(text.split_off(trimmed.len() - op_len))
 }
 
-impl SelectStmtParamType for Vec<StmtParam> {
-    fn select(&self, name: &str) -> Option<&str> {
-        self.iter().find(|param| param.name == name).map(|param| param.rust_type.as_str())
-    }
+/// Checks whether the text ends within the `SET` clause, where `=` is an assignment.
+fn is_assignment(text: &str) -> bool {
+    let tokens = scan::tokenize(text);
+    tokens.iter().rev().find_map(|token| match token {
+        scan::Token::Word(word) if ["SET", "WHERE", "ON", "HAVING", "WHEN", "AND", "OR", "NOT", "USING"].iter().any(|kw| word.eq_ignore_ascii_case(kw)) => Some(*word),
+        _ => None
+    }).is_some_and(|word| word.eq_ignore_ascii_case("SET"))
 }
 
-impl StmtItem {
-    fn is_bind(&self, name: &str) -> bool {
-        match self {
-            Self::Text(_) => { false },
-            Self::Bind(param_name) => { param_name == name },
-            Self::List(param_name) => { param_name == name }
+/// Parses the comma separated allow-list of identifiers of an identifier parameter,
+/// or the list of values of an enumerated parameter.
+fn parse_choices(list: &str, pattern: &Regex, kind: &str, param_name: &str) -> Result<Vec<String>> {
+    let mut choices : Vec<String> = Vec::new();
+    for choice in list.split(',').map(str::trim) {
+        if !pattern.is_match(choice) {
+            return Err(err::new(format!("`{}` is not a valid {} in the list of `{}`", choice, kind, param_name)));
+        }
+        if choices.iter().any(|other| choice_variant(other) == choice_variant(choice)) {
+            return Err(err::new(format!("`{}` is listed more than once in the list of `{}`", choice, param_name)));
+        }
+        let variant = choice_variant(choice);
+        if syn::parse_str::<syn::Ident>(&variant).is_err() {
+            return Err(err::new(format!("`{}` cannot be used in the list of `{}` as its variant name `{}` is not a valid Rust identifier", choice, param_name, variant)));
         }
+        choices.push(choice.to_string());
     }
+    Ok(choices)
 }
 
-impl TakeStmtItem for Vec<&StmtItem> {
-    fn take_by_name(&mut self, name: &str) -> Option<&StmtItem> {
-        let pos = self.iter().position(|item|
-            match &item {
-                StmtItem::Bind(param_name) => param_name == name,
-                StmtItem::List(param_name) => param_name == name,
-                _ => false
-            }
-        );
-        pos.map(|idx| self.remove(idx))
-    }
+/// Returns the name of the enum variant that represents the allowed identifier or value.
+pub(crate) fn choice_variant(choice: &str) -> String {
+    choice.replace(['.', '-'], "_").to_camel_case()
 }
 
-trait StrExt {
-    fn to_snake_case(&self) -> String;
+/// Returns the number of elements of the declared parameter type if it is a tuple.
+fn tuple_arity(params: &[StmtParam], name: &str) -> Option<usize> {
+    let param = params.iter().find(|param| param.name == name)?;
+    match syn::parse_str::<syn::Type>(&param.rust_type) {
+        Ok(syn::Type::Tuple(tuple)) if !tuple.elems.is_empty() => Some(tuple.elems.len()),
+        _ => None
+    }
 }
 
-impl StrExt for str {
-    fn to_snake_case(&self) -> String {
-        crate::conv::to_snake_case(self)
+/// Checks whether the type is an `Option`.
+pub(crate) fn is_option(param_type: &syn::Type) -> bool {
+    match param_type {
+        syn::Type::Path(type_path) => type_path.path.segments.last().map(|segment| segment.ident == "Option").unwrap_or_default(),
+        _ => false
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::sql::StmtItem;
-
-    #[test]
-    fn bind_name() {
-        use super::BIND_NAME;
-
-        let mut iter = BIND_NAME.captures_iter("where col1 = :val1 and col2 in ( :val2 ) and col3 = :val3 and ...");
-        let cap = iter.next();
-        assert!(cap.is_some());
-        let cap = cap.unwrap();
-        let g = cap.get(1);
-        assert!(g.is_none());
-        let g = cap.get(2);
-        assert!(g.is_some());
-        let g = g.unwrap();
-        assert_eq!(g.as_str(), ":val1");
-
-        let cap = iter.next();
-        assert!(cap.is_some());
-        let cap = cap.unwrap();
-        let g = cap.get(1);
-        assert!(g.is_some());
-        let g = g.unwrap();
-        assert_eq!(g.as_str(), ":val2");
-        let g = cap.get(2);
-        assert!(g.is_none());
-
-        let cap = iter.next();
-        assert!(cap.is_some());
-        let cap = cap.unwrap();
-        let g = cap.get(1);
-        assert!(g.is_none());
-        let g = cap.get(2);
-        assert!(g.is_some());
-        let g = g.unwrap();
-        assert_eq!(g.as_str(), ":val3");
+/// Adds a predicate to the (optional) `cfg` predicate.
+fn add_cfg(cfg: &mut Option<String>, pred: &str) {
+    *cfg = Some(match cfg.take() {
+        Some(cfg) => format!("all({}, {})", cfg, pred),
+        None => pred.to_string()
+    });
+}
 
-        let cap = iter.next();
-        assert!(cap.is_none());
+impl IncludedSql {
+    /// Returns the name of the type that `impl_sql` is expected to generate for the file.
+    pub(crate) fn type_name(&self) -> String {
+        let mut name = self.file_name.to_camel_case();
+        name.push_str("Sql");
+        name
     }
 
-    #[test]
-    fn parse_empty_sql_file() {
-        let text = "\n  \n  \n";
-        let sql = super::parse(text, "empty_sql_file").unwrap();
-        assert!(sql.stmt_list.is_empty());
+    /// Prefixes names of the enums of identifier and enumerated parameters with the file type name.
+    ///
+    /// Thus enums of different files do not clash with each other, and enums of parameters
+    /// like `result` do not shadow prelude types in the module that includes the file.
+    fn prefix_enum_names(&mut self) {
+        let prefix = self.type_name();
+        for param in self.stmt_list.iter_mut().flat_map(|stmt| stmt.params.iter_mut()).filter(|param| param.is_enum()) {
+            param.rust_type.insert_str(0, &prefix);
+        }
     }
 
-    #[test]
-    fn parse_comments_only_text() {
-        let text = "
--- name: commented_out_statement?
+    /// Leaves only statement variants that are appropriate for the specified SQL dialect.
+    ///
+    /// If the dialect is not specified, statement variants are gated by the `cfg`
+    /// attribute that checks whether the cargo feature with the dialect name is enabled.
+    /// The undecorated (default) variant is used when none of these features are enabled.
+    ///
+    /// Fails if a statement has neither a variant for the specified dialect nor the default one.
+    pub(crate) fn select_dialect(&mut self, dialect: Option<&str>) -> Result<()> {
+        if let Some(dialect) = dialect {
+            let selected = self.stmt_list.iter().map(|stmt|
+                match stmt.dialect.as_deref() {
+                    Some(stmt_dialect) => stmt_dialect == dialect,
+                    None => !self.has_variant(&stmt.name, dialect),
+                }
+            ).collect::<Vec<_>>();
+            for stmt in &self.stmt_list {
+                let has_selected = self.stmt_list.iter().zip(&selected).any(|(variant, is_selected)| *is_selected && variant.name == stmt.name);
+                if !has_selected {
+                    return Err(err::new(format!("statement `{}` has neither a `{}` variant nor a default one", &stmt.name, dialect)));
+                }
+            }
+            let mut is_selected = selected.into_iter();
+            self.stmt_list.retain(|_| is_selected.next().unwrap_or_default());
+            for stmt in &mut self.stmt_list {
+                stmt.dialect = None;
+            }
+        } else {
+            let mut dialects : Vec<(String, Vec<String>)> = Vec::new();
+            for stmt in &self.stmt_list {
+                if let Some(dialect) = stmt.dialect.as_ref() {
+                    match dialects.iter_mut().find(|(name, _)| name == &stmt.name) {
+                        Some((_, names)) => names.push(dialect.clone()),
+                        None => dialects.push((stmt.name.clone(), vec![dialect.clone()])),
+                    }
+                }
+            }
+            for stmt in &mut self.stmt_list {
+                if let Some(dialect) = stmt.dialect.as_ref() {
+                    let pred = format!("feature = {:?}", dialect);
+                    add_cfg(&mut stmt.cfg, &pred);
+                } else if let Some((_, names)) = dialects.iter().find(|(name, _)| name == &stmt.name) {
+                    let features = names.iter().map(|dialect| format!("feature = {:?}", dialect)).collect::<Vec<_>>();
+                    let pred = format!("not(any({}))", features.join(", "));
+                    add_cfg(&mut stmt.cfg, &pred);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces omitted variant selectors with the ones that are inferred from the statement text.
+    ///
+    /// Returns warnings about explicit variant selectors that contradict the statement text.
+    pub(crate) fn infer_variants(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for stmt in &mut self.stmt_list {
+            let Some(inferred_into) = stmt.inferred_into else { continue; };
+            if stmt.into_omitted {
+                stmt.into = inferred_into.to_string();
+            } else if stmt.into != inferred_into && ["?", "!", "->"].contains(&stmt.into.as_str()) {
+                warnings.push(format!("statement `{}` variant selector `{}` contradicts its SQL, which suggests `{}`", stmt.name, stmt.into, inferred_into));
+            }
+        }
+        warnings
+    }
+
+    fn has_variant(&self, name: &str, dialect: &str) -> bool {
+        self.stmt_list.iter().any(|stmt| stmt.name == name && stmt.dialect.as_deref() == Some(dialect))
+    }
+}
+
+impl StmtHeader {
+    fn add_cfg(&mut self, pred: &str) {
+        add_cfg(&mut self.cfg, pred);
+    }
+
+    /// Parses `a, b` list of parameter names that defines the order of statement parameters.
+    fn parse_order(&mut self, decl: &str) -> Result<()> {
+        let stmt_name = self.name.as_deref().unwrap_or_default();
+        for name in decl.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let name = name.strip_prefix(':').unwrap_or(name);
+            if syn::parse_str::<syn::Ident>(name).is_err() {
+                return Err(err::new(format!("`{}` is not a valid parameter name in `params:` of `{}`", name, stmt_name)));
+            }
+            let name = to_snake_case(name);
+            if self.order.contains(&name) {
+                return Err(err::new(format!("param `{}` is listed more than once in `params:` of `{}`", name, stmt_name)));
+            }
+            self.order.push(name);
+        }
+        Ok(())
+    }
+
+    /// Parses `reads = a, b; writes = c` table lists that override the analysis of the statement text.
+    fn parse_tables(&mut self, decl: &str) -> Result<()> {
+        for list in decl.split(';').map(str::trim).filter(|list| !list.is_empty()) {
+            let (kind, names) = list.split_once('=').map(|(kind, names)| (kind.trim(), names)).unwrap_or((list, ""));
+            let mut tables = Vec::new();
+            for name in names.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                if !TABLE_NAME.is_match(name) {
+                    return Err(err::new(format!("`{}` is not a valid table name in `{}`", name, self.name.as_deref().unwrap_or_default())));
+                }
+                tables.push(name.to_string());
+            }
+            match kind {
+                "reads" => self.reads = Some(tables),
+                "writes" => self.writes = Some(tables),
+                _ => return Err(err::new(format!("unknown table list `{}` in `{}` - expected `reads` or `writes`", kind, self.name.as_deref().unwrap_or_default())))
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that all conditional fragments are closed.
+    fn check_groups(&self) -> Result<()> {
+        if let Some(&idx) = self.open_groups.first() {
+            let name = self.name.as_deref().unwrap_or_default();
+            return Err(match &self.groups[idx] {
+                StmtGroup::Cfg(pred) => err::new(format!("`#if {}` in `{}` is not closed with `#endif`", pred, name)),
+                StmtGroup::Opt(param) => err::new(format!("`--? if :{}` in `{}` is not closed with `--? end`", param, name)),
+            });
+        }
+        Ok(())
+    }
+
+    fn open_group(&mut self, group: StmtGroup) {
+        self.groups.push(group);
+        self.open_groups.push(self.groups.len() - 1);
+    }
+
+    /// Closes the last open conditional fragment if it is the expected one.
+    fn close_group(&mut self, is_expected: impl Fn(&StmtGroup) -> bool) -> bool {
+        self.open_groups.pop().map(|idx| is_expected(&self.groups[idx])).unwrap_or_default()
+    }
+
+    fn push_doc_line(&mut self, line: &str) {
+        if !self.docs.is_empty() {
+            self.docs.push('\n');
+        }
+        self.docs.push_str(line);
+    }
+}
+
+impl StmtParam {
+    fn new(name: String, rust_type: String) -> Self {
+        Self { name, rust_type, mode: ParamMode::In, default: None, idents: Vec::new(), values: Vec::new() }
+    }
+
+    /// Checks whether the parameter type is an enum that include-sql generates.
+    pub(crate) fn is_enum(&self) -> bool {
+        !self.idents.is_empty() || !self.values.is_empty()
+    }
+}
+
+impl ParamMode {
+    fn from_keyword(keyword: Option<&str>) -> Self {
+        match keyword {
+            Some("out") => Self::Out,
+            Some("inout") => Self::InOut,
+            _ => Self::In
+        }
+    }
+}
+
+/// Splits the text that follows the parameter type into the default value and the parameter description.
+///
+/// The default value is a Rust expression that follows `=`, like `= 100 - page size`. The shortest text
+/// before ` - ` that is a valid expression is used, thus expressions that contain ` - ` themselves
+/// must be parenthesized, like `= (PAGE - 1) - first row`. When none of these is a valid expression,
+/// the whole text, including `=`, is the description.
+fn split_default(text: &str) -> (Option<String>, &str) {
+    let Some(expr_text) = text.strip_prefix('=') else {
+        return (None, text);
+    };
+    let split_points = expr_text.match_indices(" - ").map(|(pos, _)| pos);
+    for pos in split_points.chain(std::iter::once(expr_text.len())) {
+        if let Ok(expr) = syn::parse_str::<syn::Expr>(&expr_text[..pos]) {
+            return (Some(expr.into_token_stream().to_string()), expr_text[pos..].trim_start());
+        }
+    }
+    (None, text)
+}
+
+/// Splits the statement variant selector into the selector itself and the result cardinality.
+///
+/// Cardinality can be appended to the selector - `?1`, `?0..1`, `?*` - or follow it as a keyword - `:one`.
+fn split_cardinality(stmt_name: &str, selector: &str, count: Option<&str>, keyword: Option<&str>) -> Result<(Option<String>, Option<Cardinality>)> {
+    let mut into = selector;
+    let mut cardinality = match count {
+        Some("1") => Some(Cardinality::One),
+        Some("0..1") => Some(Cardinality::Optional),
+        Some(count) => return Err(err::new(format!("statement `{}` result cardinality `{}` is not supported", stmt_name, count))),
+        None => None
+    };
+    if cardinality.is_none() && !INTO_TOKEN.is_match(into) {
+        if let Some(selector) = into.strip_suffix('*').filter(|selector| !selector.is_empty()) {
+            into = selector;
+            cardinality = Some(Cardinality::Many);
+        }
+    }
+    if let Some(keyword) = keyword {
+        if cardinality.is_some() {
+            return Err(err::new(format!("statement `{}` has more than one result cardinality", stmt_name)));
+        }
+        cardinality = Cardinality::from_keyword(keyword);
+    }
+    let into = if into.is_empty() { None } else { Some(into.to_string()) };
+    Ok((into, cardinality))
+}
+
+impl Cardinality {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "one" => Some(Self::One),
+            "opt" => Some(Self::Optional),
+            "many" => Some(Self::Many),
+            "exec" => Some(Self::Exec),
+            "execrows" => Some(Self::RowsAffected),
+            _ => None
+        }
+    }
+
+    /// Returns the variant selector that is implied by the cardinality when the selector is omitted.
+    fn default_into(self) -> &'static str {
+        match self {
+            Self::One | Self::Optional | Self::Many => "?",
+            Self::Exec | Self::RowsAffected => "!",
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::One => "one",
+            Self::Optional => "optional",
+            Self::Many => "many",
+            Self::Exec => "exec",
+            Self::RowsAffected => "rows_affected",
+        }
+    }
+}
+
+/// Type aliases that are declared in the file header
+#[derive(Default)]
+struct TypeAliases {
+    aliases: Vec<(String, proc_macro2::TokenStream)>,
+}
+
+impl TypeAliases {
+    /// Adds the alias. The aliased type can use aliases that are declared before it.
+    fn add(&mut self, name: &str, rust_type: &str) -> Result<()> {
+        if syn::parse_str::<syn::Ident>(name).is_err() {
+            return Err(err::new(format!("`{}` is not a valid type alias name", name)));
+        }
+        if self.aliases.iter().any(|(alias, _)| alias == name) {
+            return Err(err::new(format!("type alias `{}` is declared more than once", name)));
+        }
+        let rust_type = self.expand(rust_type)?;
+        match syn::parse_str::<syn::Type>(&rust_type) {
+            Ok(alias_type) => self.aliases.push((name.to_string(), alias_type.into_token_stream())),
+            Err(parse_err) => return Err(err::new(format!("type alias `{}` is not a valid Rust type: {}", name, parse_err))),
+        }
+        Ok(())
+    }
+
+    /// Replaces aliases in the type with the aliased types.
+    fn expand(&self, rust_type: &str) -> Result<String> {
+        let type_name = rust_type.to_lowercase();
+        if !self.aliases.iter().any(|(alias, _)| type_name.contains(&alias.to_lowercase())) {
+            return Ok(rust_type.to_string());
+        }
+        match rust_type.parse::<proc_macro2::TokenStream>() {
+            Ok(type_tokens) => Ok(self.substitute(type_tokens)?.to_string()),
+            Err(_) => Ok(rust_type.to_string())
+        }
+    }
+
+    /// Replaces single segment paths that name aliases with the aliased types.
+    /// Path segments that are preceded or followed by `::`, and lifetimes, are left as they are.
+    /// Names that differ from an alias only by the letter case are reported as unknown aliases.
+    fn substitute(&self, tokens: proc_macro2::TokenStream) -> Result<proc_macro2::TokenStream> {
+        use proc_macro2::{Group, TokenTree};
+
+        let mut substituted = proc_macro2::TokenStream::new();
+        let mut prev_is_prefix = false;
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            let is_prefix = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == ':' || punct.as_char() == '\'');
+            let next_is_colon = matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == ':');
+            match token {
+                TokenTree::Ident(ident) if !prev_is_prefix && !next_is_colon => {
+                    match self.aliases.iter().find(|(alias, _)| ident == alias) {
+                        Some((_, alias_type)) => substituted.extend(alias_type.clone()),
+                        None => {
+                            let name = ident.to_string();
+                            if let Some((alias, _)) = self.aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(&name)) {
+                                return Err(err::new(format!("unknown type alias `{}` - did you mean `{}`?", name, alias)));
+                            }
+                            substituted.extend(Some(TokenTree::Ident(ident)));
+                        },
+                    }
+                },
+                TokenTree::Group(group) => {
+                    let mut expanded = Group::new(group.delimiter(), self.substitute(group.stream())?);
+                    expanded.set_span(group.span());
+                    substituted.extend(Some(TokenTree::Group(expanded)));
+                },
+                _ => substituted.extend(Some(token)),
+            }
+            prev_is_prefix = is_prefix;
+        }
+        Ok(substituted)
+    }
+}
+
+/// Parses a comma separated list of `name: Type` result column declarations.
+fn parse_columns(decl: &str) -> Result<Vec<StmtColumn>> {
+    let parser = syn::punctuated::Punctuated::<ColumnDecl, syn::Token![,]>::parse_terminated;
+    match parser.parse_str(decl) {
+        Ok(columns) => Ok(columns.into_iter().map(|ColumnDecl { name, rust_type }| StmtColumn {
+            name: to_snake_case(&name.to_string()),
+            rust_type: rust_type.into_token_stream().to_string(),
+        }).collect()),
+        Err(parse_err) => Err(err::new(format!("`{}` is not a valid result column declaration: {}", decl.trim(), parse_err)))
+    }
+}
+
+/// Result column declaration as it is written in SQL
+struct ColumnDecl {
+    name: syn::Ident,
+    rust_type: syn::Type,
+}
+
+impl syn::parse::Parse for ColumnDecl {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<syn::Token![:]>()?;
+        let rust_type = input.parse()?;
+        Ok(Self { name, rust_type })
+    }
+}
+
+impl StmtAttr {
+    fn new(name: String, value: String) -> Self {
+        Self { name, value }
+    }
+}
+
+impl SelectStmtParamType for Vec<StmtParam> {
+    fn select(&self, name: &str) -> Option<&str> {
+        self.iter().find(|param| param.name == name).map(|param| param.rust_type.as_str())
+    }
+}
+
+impl StmtItem {
+    /// Returns the name of the parameter of the parameter placeholder.
+    fn param_name(&self) -> Option<&str> {
+        match self {
+            Self::Bind(name) | Self::List(name) | Self::Tuples(name, _) | Self::Field(name, _) | Self::Ident(name) | Self::NullSafe(name, _) => Some(name),
+            Self::Text(_) | Self::Cfg(..) | Self::Opt(..) => None
+        }
+    }
+
+    fn is_bind(&self, name: &str) -> bool {
+        match self {
+            Self::Text(_) => { false },
+            Self::Bind(param_name) => { param_name == name },
+            Self::List(param_name) => { param_name == name },
+            Self::Tuples(param_name, _) => { param_name == name },
+            Self::Field(param_name, _) => { param_name == name },
+            Self::Ident(param_name) => { param_name == name },
+            Self::NullSafe(param_name, _) => { param_name == name },
+            Self::Cfg(_, items) => { items.iter().any(|item| item.is_bind(name)) },
+            Self::Opt(_, items) => { items.iter().any(|item| item.is_bind(name)) }
+        }
+    }
+}
+
+impl TakeStmtItem for Vec<&StmtItem> {
+    fn take_by_name(&mut self, name: &str) -> Option<&StmtItem> {
+        let pos = self.iter().position(|item|
+            match &item {
+                StmtItem::Bind(param_name) => param_name == name,
+                StmtItem::List(param_name) => param_name == name,
+                StmtItem::Tuples(param_name, _) => param_name == name,
+                StmtItem::Field(param_name, _) => param_name == name,
+                StmtItem::Ident(param_name) => param_name == name,
+                StmtItem::NullSafe(param_name, _) => param_name == name,
+                _ => false
+            }
+        );
+        pos.map(|idx| self.remove(idx))
+    }
+}
+
+trait StrExt {
+    fn to_snake_case(&self) -> String;
+}
+
+impl StrExt for str {
+    fn to_snake_case(&self) -> String {
+        crate::conv::to_snake_case(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::StmtItem;
+
+    #[test]
+    fn bind_name() {
+        use super::BIND_NAME;
+
+        let mut iter = BIND_NAME.captures_iter("where col1 = :val1 and col2 in ( :val2 ) and col3 = :val3 and ...");
+        let cap = iter.next();
+        assert!(cap.is_some());
+        let cap = cap.unwrap();
+        let g = cap.get(1);
+        assert!(g.is_none());
+        let g = cap.get(2);
+        assert!(g.is_some());
+        let g = g.unwrap();
+        assert_eq!(g.as_str(), ":val1");
+
+        let cap = iter.next();
+        assert!(cap.is_some());
+        let cap = cap.unwrap();
+        let g = cap.get(1);
+        assert!(g.is_some());
+        let g = g.unwrap();
+        assert_eq!(g.as_str(), ":val2");
+        let g = cap.get(2);
+        assert!(g.is_none());
+
+        let cap = iter.next();
+        assert!(cap.is_some());
+        let cap = cap.unwrap();
+        let g = cap.get(1);
+        assert!(g.is_none());
+        let g = cap.get(2);
+        assert!(g.is_some());
+        let g = g.unwrap();
+        assert_eq!(g.as_str(), ":val3");
+
+        let cap = iter.next();
+        assert!(cap.is_none());
+    }
+
+    #[test]
+    fn parse_empty_sql_file() {
+        let text = "\n  \n  \n";
+        let sql = super::parse(text, "empty_sql_file").unwrap();
+        assert!(sql.stmt_list.is_empty());
+    }
+
+    #[test]
+    fn parse_comments_only_text() {
+        let text = "
+-- name: commented_out_statement?
 -- SELECT *
 --   FROM some_table
 --  WHERE column_a = :val1
@@ -623,6 +1861,1205 @@ SELECT *
         }
     }
 
+    #[test]
+    fn parse_file_docs() {
+        use super::parse;
+
+        let text = "
+-- Library queries
+--
+-- Queries that manage book loans
+-- name: get_loaned_books?
+-- Returns the list of books loaned to a patron
+SELECT book_title FROM library WHERE loaned_to = :user_id
+/
+-- this comment is not a part of the file doc-comment
+        ";
+        let sql = parse(text, "file_docs").unwrap();
+        assert_eq!(sql.docs.as_ref().unwrap(), " Library queries\n\n Queries that manage book loans");
+        assert_eq!(sql.stmt_list[0].docs.as_ref().unwrap(), " Returns the list of books loaned to a patron");
+
+        let text = "
+-- name: get_loaned_books?
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let sql = parse(text, "no_file_docs").unwrap();
+        assert!(sql.docs.is_none());
+    }
+
+    #[test]
+    fn parse_stmt_attrs() {
+        use super::parse;
+
+        let text = "
+-- name: get_loaned_books?
+-- Returns the list of books loaned to a patron
+-- @timeout: 30s
+-- attr: route = replica
+-- attr: prepared
+-- @todo
+-- @ this is not an attribute
+SELECT book_title FROM library WHERE loaned_to = :user_id
+        ";
+        let sql = parse(text, "stmt_attrs").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.attrs.len(), 3);
+        assert_eq!(stmt.attrs[0].name, "timeout");
+        assert_eq!(stmt.attrs[0].value, "30s");
+        assert_eq!(stmt.attrs[1].name, "route");
+        assert_eq!(stmt.attrs[1].value, "replica");
+        assert_eq!(stmt.attrs[2].name, "prepared");
+        assert_eq!(stmt.attrs[2].value, "");
+        assert_eq!(stmt.docs.as_ref().unwrap(), " Returns the list of books loaned to a patron\n @todo\n @ this is not an attribute");
+    }
+
+    #[test]
+    fn parse_rust_attrs() {
+        use super::parse;
+
+        let text = "
+-- #[must_use]
+-- name: find_user?
+-- Returns user's record
+-- #[deprecated(note = \"use find_user_v2\")]
+-- #[doc(hidden)] #[allow(dead_code)]
+SELECT * FROM users WHERE user_id = :user_id
+/
+-- name: find_user_v2?
+SELECT * FROM users WHERE user_id = :user_id AND active
+        ";
+        let sql = parse(text, "rust_attrs").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.rust_attrs, ["#[must_use]", "#[deprecated(note = \"use find_user_v2\")]", "#[doc(hidden)] #[allow(dead_code)]"]);
+        assert_eq!(stmt.docs.as_ref().unwrap(), " Returns user's record");
+        assert!(sql.stmt_list[1].rust_attrs.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a valid Rust attribute")]
+    fn parse_bad_rust_attr() {
+        use super::parse;
+
+        let text = "
+-- name: find_user?
+-- #[deprecated(note = \"use find_user_v2\"]
+SELECT * FROM users WHERE user_id = :user_id
+        ";
+        parse(text, "bad_rust_attr").unwrap();
+    }
+
+    #[test]
+    fn parse_conditional_fragments() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+-- cfg: feature = \"postgres\"
+-- cfg: not(test)
+SELECT book_title
+  FROM library
+ WHERE 1 = 1
+-- #if feature = \"audit\"
+   AND audited_by = :auditor
+-- #if feature = \"strict\"
+   AND audited_on IS NOT NULL
+-- #endif
+-- #endif
+   AND book_title LIKE :title
+        ";
+        let sql = parse(text, "conditional_fragments").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.cfg.as_ref().unwrap(), "all(feature = \"postgres\", not(test))");
+        assert_eq!(stmt.items.len(), 4);
+        match &stmt.items[0] {
+            StmtItem::Text(text) => assert_eq!(text, "SELECT book_title\n  FROM library\n WHERE 1 = 1"),
+            item => panic!("unexpected {:?}", item)
+        }
+        match &stmt.items[1] {
+            StmtItem::Cfg(pred, items) => {
+                assert_eq!(pred, "feature = \"audit\"");
+                assert_eq!(items.len(), 3);
+                match &items[1] {
+                    StmtItem::Bind(name) => assert_eq!(name, "auditor"),
+                    item => panic!("unexpected {:?}", item)
+                }
+                match &items[2] {
+                    StmtItem::Cfg(pred, items) => {
+                        assert_eq!(pred, "feature = \"strict\"");
+                        assert_eq!(items.len(), 1);
+                    },
+                    item => panic!("unexpected {:?}", item)
+                }
+            },
+            item => panic!("unexpected {:?}", item)
+        }
+        match &stmt.items[2] {
+            StmtItem::Text(text) => assert_eq!(text, "\n   AND book_title LIKE "),
+            item => panic!("unexpected {:?}", item)
+        }
+        let binds = stmt.unique_binds();
+        assert_eq!(binds.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "`#if feature = \\\"audit\\\"` in `find_books` is not closed with `#endif`")]
+    fn parse_unclosed_conditional_fragment() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+SELECT book_title FROM library
+-- #if feature = \"audit\"
+ WHERE audited
+/
+        ";
+        parse(text, "unclosed_conditional_fragment").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "`#endif` without matching `#if` in `find_books`")]
+    fn parse_unmatched_endif() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+SELECT book_title FROM library
+-- #endif
+        ";
+        parse(text, "unmatched_endif").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "`param: a: i32` must be placed before the first conditional fragment of `get_books`")]
+    fn parse_param_in_conditional_fragment() {
+        use super::parse;
+
+        let text = "
+-- name: get_books?
+-- #if feature = \"audit\"
+-- param: a: i32
+SELECT book_title FROM library WHERE audited = :a
+-- #endif
+        ";
+        parse(text, "param_in_conditional_fragment").unwrap();
+    }
+
+    #[test]
+    fn parse_dialect_variants() {
+        use super::parse;
+
+        let text = "
+-- name: upsert_user! [postgres]
+INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO UPDATE SET name = excluded.name
+/
+-- name: get_user?
+SELECT * FROM users WHERE id = :id
+/
+-- name: upsert_user! [sqlite]
+INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO UPDATE SET name = excluded.name
+/
+-- name: upsert_user!
+MERGE INTO users USING (SELECT :id AS id, :name AS name) src ON users.id = src.id
+WHEN MATCHED THEN UPDATE SET name = src.name WHEN NOT MATCHED THEN INSERT (id, name) VALUES (src.id, src.name)
+        ";
+        let sql = parse(text, "dialect_variants").unwrap();
+        let stmts = sql.stmt_list.iter().map(|stmt| (stmt.name.as_str(), stmt.dialect.as_deref())).collect::<Vec<_>>();
+        assert_eq!(stmts, [("upsert_user", Some("postgres")), ("upsert_user", Some("sqlite")), ("upsert_user", None), ("get_user", None)]);
+
+        let mut sql = parse(text, "dialect_variants").unwrap();
+        sql.select_dialect(Some("sqlite")).unwrap();
+        assert_eq!(sql.stmt_list.len(), 2);
+        assert_eq!(sql.stmt_list[0].name, "upsert_user");
+        assert!(sql.stmt_list[0].dialect.is_none());
+        assert!(sql.stmt_list[0].items.iter().any(|item| matches!(item, StmtItem::Text(text) if text.contains("ON CONFLICT"))));
+
+        let mut sql = parse(text, "dialect_variants").unwrap();
+        sql.select_dialect(Some("oracle")).unwrap();
+        assert_eq!(sql.stmt_list.len(), 2);
+        assert!(sql.stmt_list[0].items.iter().any(|item| matches!(item, StmtItem::Text(text) if text.starts_with("MERGE"))));
+
+        let mut sql = parse(text, "dialect_variants").unwrap();
+        sql.select_dialect(None).unwrap();
+        let cfgs = sql.stmt_list.iter().map(|stmt| stmt.cfg.as_deref()).collect::<Vec<_>>();
+        assert_eq!(cfgs, [
+            Some("feature = \"postgres\""),
+            Some("feature = \"sqlite\""),
+            Some("not(any(feature = \"postgres\", feature = \"sqlite\"))"),
+            None
+        ]);
+
+        let text = "
+-- name: upsert_user! [postgres]
+INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO UPDATE SET name = excluded.name
+/
+-- name: get_user?
+SELECT * FROM users WHERE id = :id
+        ";
+        let mut sql = parse(text, "dialect_variants").unwrap();
+        let res = sql.select_dialect(Some("sqlite"));
+        assert_eq!(res.unwrap_err().to_string(), "statement `upsert_user` has neither a `sqlite` variant nor a default one");
+    }
+
+    #[test]
+    #[should_panic(expected = "statement `upsert_user` is defined more than once for `postgres`")]
+    fn parse_duplicate_dialect_variants() {
+        use super::parse;
+
+        let text = "
+-- name: upsert_user! [postgres]
+INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO UPDATE SET name = excluded.name
+/
+-- name: upsert_user! [ postgres ]
+INSERT INTO users (id, name) VALUES (:id, :name) ON CONFLICT (id) DO NOTHING
+        ";
+        parse(text, "duplicate_dialect_variants").unwrap();
+    }
+
+    #[test]
+    fn parse_optional_fragments() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+SELECT book_title
+  FROM library
+ WHERE 1 = 1
+--? if :status
+   AND status = :status
+--? end
+--? if :min_year
+   AND published BETWEEN :min_year AND :max_year
+--? end
+   AND (:status IS NULL OR :max_year IS NULL)
+        ";
+        let sql = parse(text, "optional_fragments").unwrap();
+        let stmt = &sql.stmt_list[0];
+        match &stmt.items[1] {
+            StmtItem::Opt(param, items) => {
+                assert_eq!(param, "status");
+                assert_eq!(items.len(), 2);
+            },
+            item => panic!("unexpected {:?}", item)
+        }
+        match &stmt.items[2] {
+            StmtItem::Opt(param, items) => {
+                assert_eq!(param, "min_year");
+                assert_eq!(items.len(), 4);
+            },
+            item => panic!("unexpected {:?}", item)
+        }
+        assert!(stmt.is_optional("min_year"));
+        assert!(!stmt.is_optional("status"));
+        assert!(!stmt.is_optional("max_year"));
+    }
+
+    #[test]
+    #[should_panic(expected = "optional fragment parameter `status` is not used in its fragment in `find_books`")]
+    fn parse_unused_optional_fragment_param() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+SELECT book_title
+  FROM library
+ WHERE book_title LIKE :title
+--? if :status
+   AND returned_on IS NULL
+--? end
+        ";
+        parse(text, "unused_optional_fragment_param").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "`--? end` without matching `--? if` in `find_books`")]
+    fn parse_mismatched_optional_fragment() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+SELECT book_title
+  FROM library
+ WHERE book_title LIKE :title
+-- #if feature = \"audit\"
+   AND audited
+--? end
+        ";
+        parse(text, "mismatched_optional_fragment").unwrap();
+    }
+
+    #[test]
+    fn parse_fragments() {
+        use super::parse;
+
+        let text = "
+-- name: get_user?
+SELECT {{user_columns}}
+  FROM users
+ WHERE user_id = :user_id
+-- include: tenant_scope
+/
+-- fragment: user_columns
+-- Columns that are selected for user records
+user_id, user_name, {{ audit_columns }}
+/
+-- fragment: audit_columns
+created_on, updated_on
+/
+-- fragment: tenant_scope
+   AND tenant_id = :tenant_id
+-- #if feature = \"soft-delete\"
+   AND deleted_on IS NULL
+-- #endif
+/
+-- name: find_users?
+SELECT {{user_columns}} FROM users
+ WHERE user_name LIKE :name
+--? if :status
+   AND status = :status
+--? end
+-- include: tenant_scope
+        ";
+        let sql = parse(text, "fragments").unwrap();
+        assert_eq!(sql.stmt_list.len(), 2);
+
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.name, "get_user");
+        match &stmt.items[0] {
+            StmtItem::Text(text) => assert_eq!(text, "SELECT user_id, user_name, created_on, updated_on\n  FROM users\n WHERE user_id = "),
+            item => panic!("unexpected {:?}", item)
+        }
+        match &stmt.items[4] {
+            StmtItem::Cfg(pred, _) => assert_eq!(pred, "feature = \"soft-delete\""),
+            item => panic!("unexpected {:?}", item)
+        }
+        let binds = stmt.unique_binds();
+        assert_eq!(binds.len(), 2);
+
+        let stmt = &sql.stmt_list[1];
+        assert_eq!(stmt.name, "find_users");
+        let groups = stmt.items.iter().filter_map(|item| match item {
+            StmtItem::Opt(param, _) => Some(param.as_str()),
+            StmtItem::Cfg(pred, _) => Some(pred.as_str()),
+            _ => None
+        }).collect::<Vec<_>>();
+        assert_eq!(groups, ["status", "feature = \"soft-delete\""]);
+    }
+
+    #[test]
+    #[should_panic(expected = "fragment `user_columns` is not defined")]
+    fn parse_unknown_fragment() {
+        use super::parse;
+
+        let text = "
+-- name: get_user?
+SELECT {{user_columns}} FROM users WHERE user_id = :user_id
+        ";
+        parse(text, "unknown_fragment").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "fragment `a` includes itself via `a` -> `b` -> `a`")]
+    fn parse_recursive_fragments() {
+        use super::parse;
+
+        let text = "
+-- fragment: a
+x, {{b}}
+/
+-- fragment: b
+y, {{a}}
+/
+-- name: get_something?
+SELECT {{a}} FROM somewhere
+        ";
+        parse(text, "recursive_fragments").unwrap();
+    }
+
+    #[test]
+    fn parse_result_columns() {
+        use super::parse;
+
+        let text = "
+-- name: get_loaned_books?
+-- Returns books loaned to a patron
+-- returns: id: i64, title: String,
+-- column: name: Option<String>
+-- column: loanedOn: Option<(u16, u8, u8)>
+-- param: user_id: &str - user ID
+SELECT book_id, title, name, loaned_on FROM library WHERE loaned_to = :user_id
+        ";
+        let sql = parse(text, "result_columns").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.name, "get_loaned_books");
+        let columns = stmt.columns.iter().map(|column| (column.name.as_str(), column.rust_type.as_str())).collect::<Vec<_>>();
+        assert_eq!(columns, [("id", "i64"), ("title", "String"), ("name", "Option < String >"), ("loaned_on", "Option < (u16 , u8 , u8) >")]);
+        assert_eq!(stmt.docs.as_deref(), Some(" Returns books loaned to a patron\n * `user_id` - user ID"));
+
+        let text = "
+-- name: get_book_count?
+SELECT count(*) FROM library
+/
+-- returns: id: i64
+-- column: title: String
+-- name: get_books?
+-- returns: author: String
+SELECT book_id, title, author FROM library
+        ";
+        let sql = parse(text, "result_columns").unwrap();
+        assert!(sql.stmt_list[0].columns.is_empty());
+        let columns = sql.stmt_list[1].columns.iter().map(|column| column.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(columns, ["id", "title", "author"]);
+
+        let text = "
+-- name: get_books?
+-- returns: the list of books
+SELECT book_id, title, author FROM library
+        ";
+        let sql = parse(text, "result_columns").unwrap();
+        assert!(sql.stmt_list[0].columns.is_empty());
+        assert_eq!(sql.stmt_list[0].docs.as_deref(), Some(" returns: the list of books"));
+    }
+
+    #[test]
+    #[should_panic(expected = "`id: i64, title: String<` is not a valid result column declaration")]
+    fn parse_bad_result_columns() {
+        use super::parse;
+
+        let text = "
+-- name: get_loaned_books?
+-- returns: id: i64, title: String<
+SELECT book_id, title FROM library
+        ";
+        parse(text, "bad_result_columns").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "result column `title` is declared more than once in `get_loaned_books`")]
+    fn parse_duplicate_result_columns() {
+        use super::parse;
+
+        let text = "
+-- name: get_loaned_books?
+-- returns: id: i64, title: String
+-- column: title: String
+SELECT book_id, title FROM library
+        ";
+        parse(text, "duplicate_result_columns").unwrap();
+    }
+
+    #[test]
+    fn parse_cardinality() {
+        use super::{parse, Cardinality};
+
+        let text = "
+-- name: get_user?1
+SELECT * FROM users WHERE id = :id
+/
+-- name: find_user?0..1
+SELECT * FROM users WHERE email = :email
+/
+-- name: get_users?* [postgres]
+SELECT * FROM users
+/
+-- name: get_name :one
+SELECT name FROM users WHERE id = :id
+/
+-- name: delete_users :execrows
+DELETE FROM users WHERE status = :status
+/
+-- name: add_user-> :one
+INSERT INTO users (name) VALUES (:name) RETURNING id
+/
+-- name: get_all?
+SELECT * FROM users
+/
+-- name: multiply*
+SELECT 1
+        ";
+        let sql = parse(text, "cardinality").unwrap();
+        let stmts = sql.stmt_list.iter().map(|stmt| (stmt.name.as_str(), stmt.into.as_str(), stmt.cardinality)).collect::<Vec<_>>();
+        assert_eq!(stmts, [
+            ("get_user", "?", Some(Cardinality::One)),
+            ("find_user", "?", Some(Cardinality::Optional)),
+            ("get_users", "?", Some(Cardinality::Many)),
+            ("get_name", "?", Some(Cardinality::One)),
+            ("delete_users", "!", Some(Cardinality::RowsAffected)),
+            ("add_user", "->", Some(Cardinality::One)),
+            ("get_all", "?", None),
+            ("multiply", "*", None),
+        ]);
+        assert_eq!(sql.stmt_list[2].dialect.as_deref(), Some("postgres"));
+    }
+
+    #[test]
+    #[should_panic(expected = "statement `get_users` result cardinality `2` is not supported")]
+    fn parse_unsupported_cardinality() {
+        use super::parse;
+
+        let text = "
+-- name: get_users?2
+SELECT * FROM users
+        ";
+        parse(text, "unsupported_cardinality").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "statement `get_user` has more than one result cardinality")]
+    fn parse_conflicting_cardinality() {
+        use super::parse;
+
+        let text = "
+-- name: get_user?1 :opt
+SELECT * FROM users WHERE id = :id
+        ";
+        parse(text, "conflicting_cardinality").unwrap();
+    }
+
+    #[test]
+    fn infer_variants() {
+        use super::parse;
+
+        let text = "
+-- name: get_users
+SELECT * FROM users
+/
+-- name: add_user
+INSERT INTO users (name) VALUES (:name) RETURNING id
+/
+-- name: delete_user
+DELETE FROM users WHERE id = :id
+/
+-- name: count_users!
+SELECT count(*) FROM users
+/
+-- name: lock_users
+LOCK TABLE users
+        ";
+        let mut sql = parse(text, "infer_variants").unwrap();
+        let warnings = sql.infer_variants();
+        let variants = sql.stmt_list.iter().map(|stmt| (stmt.name.as_str(), stmt.into.as_str())).collect::<Vec<_>>();
+        assert_eq!(variants, [("get_users", "?"), ("add_user", "->"), ("delete_user", "!"), ("count_users", "!"), ("lock_users", "!")]);
+        assert_eq!(warnings, ["statement `count_users` variant selector `!` contradicts its SQL, which suggests `?`"]);
+    }
+
+    #[test]
+    fn parse_tables() {
+        use super::parse;
+
+        let text = "
+-- name: archive_loans!
+-- tables: reads = loans; writes = archive.loans, loans
+WITH moved AS (DELETE FROM loans RETURNING *) INSERT INTO archive.loans SELECT * FROM moved
+/
+-- name: get_loans?
+-- tables: books and loans
+SELECT b.title FROM books b JOIN loans l ON l.book_id = b.id
+        ";
+        let sql = parse(text, "tables").unwrap();
+        assert_eq!(sql.stmt_list[0].reads, ["loans"]);
+        assert_eq!(sql.stmt_list[0].writes, ["archive.loans", "loans"]);
+        assert_eq!(sql.stmt_list[1].reads, ["books", "loans"]);
+        assert_eq!(sql.stmt_list[1].docs.as_deref(), Some(" tables: books and loans"));
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown table list `updates` in `archive_loans` - expected `reads` or `writes`")]
+    fn parse_bad_tables() {
+        use super::parse;
+
+        let text = "
+-- name: archive_loans!
+-- tables: reads = loans; updates = archive
+INSERT INTO archive SELECT * FROM loans
+        ";
+        parse(text, "bad_tables").unwrap();
+    }
+
+    #[test]
+    fn parse_param_modes() {
+        use super::{parse, ParamMode};
+
+        let text = "
+-- name: transfer!
+-- param: from: i64 - source account
+-- param: amount: in f64 - amount to transfer
+-- param: new_balance: out f64 - resulting balance
+-- param: fee: inout f64 - transfer fee
+CALL transfer(:from, :amount, :new_balance, :fee)
+        ";
+        let sql = parse(text, "param_modes").unwrap();
+        let stmt = &sql.stmt_list[0];
+        let params = stmt.params.iter().map(|param| (param.name.as_str(), param.rust_type.as_str(), param.mode)).collect::<Vec<_>>();
+        assert_eq!(params, [
+            ("from", "i64", ParamMode::In),
+            ("amount", "f64", ParamMode::In),
+            ("new_balance", "f64", ParamMode::Out),
+            ("fee", "f64", ParamMode::InOut),
+        ]);
+        assert!(stmt.docs.as_deref().unwrap_or_default().contains(" * `new_balance` - resulting balance"));
+    }
+
+    #[test]
+    #[should_panic(expected = "IN list param `ids` cannot be an output parameter in `get_books`")]
+    fn parse_output_list_param() {
+        use super::parse;
+
+        let text = "
+-- name: get_books?
+-- param: ids: out i64 - book IDs
+SELECT * FROM books WHERE id IN (:ids)
+        ";
+        parse(text, "output_list_param").unwrap();
+    }
+
+    #[test]
+    fn parse_tuple_lists() {
+        use super::{parse, StmtItem};
+
+        let text = "
+-- name: add_books!
+-- param: rows: &NewBook - books to add
+INSERT INTO books (title, author_id, published) VALUES :rows(title, authorId, published)
+
+-- name: get_loans?
+-- param: pairs: (i32, &str) - book ID and borrower pairs
+SELECT * FROM loans WHERE (book_id, borrower) IN (:pairs)
+
+-- name: add_ratings!
+-- param: ratings: (i32, f32)
+INSERT INTO ratings (book_id, rating) VALUES :ratings(book_id, rating) ON CONFLICT DO NOTHING
+        ";
+        let sql = parse(text, "tuple_lists").unwrap();
+        assert_eq!(sql.stmt_list.len(), 3);
+
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.items.len(), 2);
+        assert!(matches!(&stmt.items[0], StmtItem::Text(text) if text == "INSERT INTO books (title, author_id, published) VALUES "));
+        assert!(matches!(&stmt.items[1], StmtItem::Tuples(name, fields) if name == "rows" && fields == &["title", "author_id", "published"]));
+
+        let stmt = &sql.stmt_list[1];
+        assert_eq!(stmt.params[0].rust_type, "(i32, &str)");
+        assert_eq!(stmt.items.len(), 3);
+        assert!(matches!(&stmt.items[1], StmtItem::Tuples(name, fields) if name == "pairs" && fields == &["0", "1"]));
+        assert!(matches!(&stmt.items[2], StmtItem::Text(text) if text == ")"));
+
+        let stmt = &sql.stmt_list[2];
+        assert_eq!(stmt.items.len(), 3);
+        assert!(matches!(&stmt.items[1], StmtItem::Tuples(name, fields) if name == "ratings" && fields == &["0", "1"]));
+        assert!(matches!(&stmt.items[2], StmtItem::Text(text) if text == " ON CONFLICT DO NOTHING"));
+    }
+
+    #[test]
+    #[should_panic(expected = "tuple list param `rows` has 2 fields, but its type has 3 elements in `add_books`")]
+    fn parse_mismatched_tuple_list() {
+        use super::parse;
+
+        let text = "
+-- name: add_books!
+-- param: rows: (&str, i32, i16)
+INSERT INTO books (title, author_id) VALUES :rows(title, author_id)
+        ";
+        parse(text, "mismatched_tuple_list").unwrap();
+    }
+
+    #[test]
+    fn parse_struct_fields() {
+        use super::{parse, StmtItem};
+
+        let text = "
+-- name: add_user!
+-- param: user: &NewUser - user to add
+INSERT INTO users (name, email, city) VALUES (:user.name, :user.email, :user.address.city)
+        ";
+        let sql = parse(text, "struct_fields").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.items.len(), 7);
+        assert!(matches!(&stmt.items[1], StmtItem::Field(name, path) if name == "user" && path == &["name"]));
+        assert!(matches!(&stmt.items[3], StmtItem::Field(name, path) if name == "user" && path == &["email"]));
+        assert!(matches!(&stmt.items[5], StmtItem::Field(name, path) if name == "user" && path == &["address", "city"]));
+        assert!(matches!(&stmt.items[6], StmtItem::Text(text) if text == ")"));
+        assert_eq!(stmt.unique_binds().len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "struct param `user` must be declared by `param:` in `add_user`")]
+    fn parse_undeclared_struct_param() {
+        use super::parse;
+
+        let text = "
+-- name: add_user!
+INSERT INTO users (name, email) VALUES (:user.name, :user.email)
+        ";
+        parse(text, "undeclared_struct_param").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "struct field placeholder `:user.name` cannot be marked as nullable in `add_user`")]
+    fn parse_nullable_struct_field() {
+        use super::parse;
+
+        let text = "
+-- name: add_user!
+-- param: user: &NewUser
+INSERT INTO users (name, email) VALUES (:user.name?, :user.email)
+        ";
+        parse(text, "nullable_struct_field").unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "struct param `user` is also used as a value in `add_user`")]
+    fn parse_struct_param_as_value() {
+        use super::parse;
+
+        let text = "
+-- name: add_user!
+-- param: user: &NewUser
+INSERT INTO users (name, email) VALUES (:user.name, :user)
+        ";
+        parse(text, "struct_param_as_value").unwrap();
+    }
+
+    #[test]
+    fn parse_ident_params() {
+        use super::{parse, StmtItem};
+
+        let text = "
+-- name: get_books?
+-- param: sort_col: ident(title, author, b.loaned_on) - sort column
+-- param: sort_dir: ident(ASC, DESC)
+SELECT * FROM books b WHERE author = :author ORDER BY :!sort_col :!sortDir
+        ";
+        let sql = parse(text, "ident_params").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.params[0].rust_type, "IdentParamsSqlSortCol");
+        assert_eq!(stmt.params[0].idents, ["title", "author", "b.loaned_on"]);
+        assert_eq!(stmt.params[1].rust_type, "IdentParamsSqlSortDir");
+        assert_eq!(stmt.params[1].idents, ["ASC", "DESC"]);
+        assert!(matches!(&stmt.items[3], StmtItem::Ident(name) if name == "sort_col"));
+        assert!(matches!(&stmt.items[5], StmtItem::Ident(name) if name == "sort_dir"));
+        assert_eq!(stmt.docs.as_deref(), Some(" * `sort_col` - sort column\n * `sort_dir` "));
+    }
+
+    #[test]
+    fn parse_bad_ident_params() {
+        use super::parse;
+
+        let text = "
+-- name: get_books?
+SELECT * FROM books ORDER BY :!sort_col
+        ";
+        let res = parse(text, "bad_ident_params");
+        assert_eq!(res.unwrap_err().to_string(), "identifier param `sort_col` must be declared with `ident(...)` allow-list in `get_books`");
+
+        let text = "
+-- name: get_books?
+-- param: sort_col: ident(title, author)
+SELECT * FROM books ORDER BY :sort_col
+        ";
+        let res = parse(text, "bad_ident_params");
+        assert_eq!(res.unwrap_err().to_string(), "param `sort_col` has an allow-list of identifiers and must be used as `:!sort_col` in `get_books`");
+
+        let text = "
+-- name: get_books?
+-- param: sort_col: ident(title, author)
+SELECT * FROM books ORDER BY :!sort_col?
+        ";
+        let res = parse(text, "bad_ident_params");
+        assert_eq!(res.unwrap_err().to_string(), "identifier placeholder `:!sort_col` cannot be marked as nullable in `get_books`");
+
+        let text = "
+-- name: get_books?
+-- param: sort_col: ident(title, 'author')
+SELECT * FROM books ORDER BY :!sort_col
+        ";
+        let res = parse(text, "bad_ident_params");
+        assert_eq!(res.unwrap_err().to_string(), "`'author'` is not a valid identifier in the list of `sort_col`");
+
+        let text = "
+-- name: get_books?
+-- param: sort_col: ident(title, author)
+SELECT * FROM books ORDER BY :!sort_col
+
+-- name: get_authors?
+-- param: sort_col: ident(name, born)
+SELECT * FROM authors ORDER BY :!sort_col
+        ";
+        let res = parse(text, "bad_ident_params");
+        assert_eq!(res.unwrap_err().to_string(), "param `sort_col` is declared with different lists of identifiers or values");
+    }
+
+    #[test]
+    fn parse_enum_params() {
+        use super::parse;
+
+        let text = "
+-- name: set_status!
+-- param: status: enum(active, suspended, on-hold) - account status
+UPDATE accounts SET status = :status WHERE id = :id
+        ";
+        let sql = parse(text, "enum_params").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.params[0].rust_type, "EnumParamsSqlStatus");
+        assert_eq!(stmt.params[0].values, ["active", "suspended", "on-hold"]);
+        assert!(stmt.params[0].is_enum());
+        assert_eq!(stmt.docs.as_deref(), Some(" * `status` - account status"));
+
+        let text = "
+-- name: set_status!
+-- param: status: enum(active, on_hold, on-hold)
+UPDATE accounts SET status = :status WHERE id = :id
+        ";
+        let res = parse(text, "enum_params");
+        assert_eq!(res.unwrap_err().to_string(), "`on-hold` is listed more than once in the list of `status`");
+
+        let text = "
+-- name: set_status!
+-- param: status: enum(active, 'closed')
+UPDATE accounts SET status = :status WHERE id = :id
+        ";
+        let res = parse(text, "enum_params");
+        assert_eq!(res.unwrap_err().to_string(), "`'closed'` is not a valid value in the list of `status`");
+
+        let text = "
+-- name: set_owner!
+-- param: owner: enum(self, team, other)
+UPDATE accounts SET owner = :owner WHERE id = :id
+        ";
+        let res = parse(text, "enum_params");
+        assert_eq!(res.unwrap_err().to_string(), "`self` cannot be used in the list of `owner` as its variant name `Self` is not a valid Rust identifier");
+    }
+
+    #[test]
+    fn parse_type_aliases() {
+        use super::parse;
+
+        let text = "
+-- Library
+-- type: UserId = &uuid::Uuid
+-- type: Timestamp = chrono::DateTime<chrono::Utc>
+-- type: Ids = &[UserId]
+
+-- name: get_loans?
+-- param: user_id: UserId - borrower
+-- param: since: Option<Timestamp>
+-- param: ids: Ids
+-- returns: id: i64, loaned_on: Timestamp, user: UserId
+SELECT * FROM loans WHERE user_id = :user_id AND loaned_on > :since AND co_borrower IN (:ids)
+        ";
+        let sql = parse(text, "type_aliases").unwrap();
+        assert_eq!(sql.docs.as_deref(), Some(" Library"));
+        let stmt = &sql.stmt_list[0];
+        let params = stmt.params.iter().map(|param| param.rust_type.as_str()).collect::<Vec<_>>();
+        assert_eq!(params, ["& uuid :: Uuid", "Option < chrono :: DateTime < chrono :: Utc > >", "& [& uuid :: Uuid]"]);
+        let columns = stmt.columns.iter().map(|column| column.rust_type.as_str()).collect::<Vec<_>>();
+        assert_eq!(columns, ["i64", "chrono :: DateTime < chrono :: Utc >", "& uuid :: Uuid"]);
+    }
+
+    #[test]
+    fn parse_bad_type_aliases() {
+        use super::parse;
+
+        let text = "
+-- type: UserId = &uuid::Uuid +
+-- name: get_user?
+SELECT * FROM users WHERE id = :id
+        ";
+        let res = parse(text, "bad_type_aliases");
+        assert!(res.unwrap_err().to_string().starts_with("type alias `UserId` is not a valid Rust type"));
+
+        let text = "
+-- type: user-id = &uuid::Uuid
+-- name: get_user?
+SELECT * FROM users WHERE id = :id
+        ";
+        let res = parse(text, "bad_type_aliases");
+        assert_eq!(res.unwrap_err().to_string(), "`user-id` is not a valid type alias name");
+
+        let text = "
+-- type: UserId = &uuid::Uuid
+-- type: UserId = i64
+-- name: get_user?
+SELECT * FROM users WHERE id = :id
+        ";
+        let res = parse(text, "bad_type_aliases");
+        assert_eq!(res.unwrap_err().to_string(), "type alias `UserId` is declared more than once");
+
+        let text = "
+-- name: get_user?
+-- type: UserId = &uuid::Uuid
+SELECT * FROM users WHERE id = :id
+        ";
+        let res = parse(text, "bad_type_aliases");
+        assert_eq!(res.unwrap_err().to_string(), "`type: UserId` must be placed in the file header before the first statement");
+
+        let text = "
+-- type: UserId = &uuid::Uuid
+-- name: get_user?
+-- param: id: UserID
+SELECT * FROM users WHERE id = :id
+        ";
+        let res = parse(text, "bad_type_aliases");
+        assert_eq!(res.unwrap_err().to_string(), "unknown type alias `UserID` - did you mean `UserId`?");
+
+        let text = "
+-- type: UserId = &uuid::Uuid
+-- param: tenant_id: &TenantId - tenant scope
+-- name: add_user!
+-- param: id: UserId
+-- param: user: &NewUser
+-- returns: added_on: Option<DateTime<Utc>>
+INSERT INTO users (id, tenant_id, name) VALUES (:id, :tenant_id, :user.name) RETURNING added_on
+        ";
+        let sql = parse(text, "bad_type_aliases").unwrap();
+        let params = sql.stmt_list[0].params.iter().map(|param| param.rust_type.as_str()).collect::<Vec<_>>();
+        assert_eq!(params, ["& uuid :: Uuid", "&NewUser", "&TenantId"]);
+        assert_eq!(sql.stmt_list[0].columns[0].rust_type, "Option < DateTime < Utc > >");
+    }
+
+    #[test]
+    fn parse_shared_params() {
+        use super::parse;
+
+        let text = "
+-- Library queries
+-- param: tenant_id: &TenantId - tenant scope
+-- param: user_id: i64 - user ID
+
+-- name: get_books?
+-- Returns loaned books
+-- param: status: &str - loan status
+SELECT * FROM books WHERE tenant_id = :tenant_id AND status = :status
+/
+
+-- name: get_user?
+-- param: user_id: &str - user name
+SELECT * FROM users WHERE tenant_id = :tenant_id AND id = :user_id
+/
+
+-- name: get_genres?
+SELECT * FROM genres
+        ";
+        let sql = parse(text, "shared_params").unwrap();
+        assert_eq!(sql.docs.as_deref(), Some(" Library queries"));
+
+        let stmt = &sql.stmt_list[0];
+        let params = stmt.params.iter().map(|param| (param.name.as_str(), param.rust_type.as_str())).collect::<Vec<_>>();
+        assert_eq!(params, [("status", "&str"), ("tenant_id", "&TenantId")]);
+        assert_eq!(stmt.docs.as_deref(), Some(" Returns loaned books\n * `status` - loan status\n * `tenant_id` - tenant scope"));
+
+        let stmt = &sql.stmt_list[1];
+        let params = stmt.params.iter().map(|param| (param.name.as_str(), param.rust_type.as_str())).collect::<Vec<_>>();
+        assert_eq!(params, [("user_id", "&str"), ("tenant_id", "&TenantId")]);
+        assert_eq!(stmt.docs.as_deref(), Some(" * `user_id` - user name\n * `tenant_id` - tenant scope"));
+
+        let stmt = &sql.stmt_list[2];
+        assert!(stmt.params.is_empty());
+        assert!(stmt.docs.is_none());
+
+        let text = "
+-- param: tenant_id: &TenantId - tenant scope
+-- param: tenant_id: i64
+
+-- name: get_books?
+SELECT * FROM books WHERE tenant_id = :tenant_id
+        ";
+        let res = parse(text, "shared_params");
+        assert_eq!(res.unwrap_err().to_string(), "shared param `tenant_id` is declared more than once");
+    }
+
+    #[test]
+    fn parse_param_order() {
+        use super::parse;
+
+        let text = "
+-- name: loan_books!
+-- params: user_id, book_ids
+-- param: book_ids: i64 - book IDs
+UPDATE library SET loaned_to = :user_id WHERE book_id IN (:book_ids)
+        ";
+        let sql = parse(text, "param_order").unwrap();
+        assert_eq!(sql.stmt_list[0].order, ["user_id", "book_ids"]);
+
+        let text = "
+-- name: loan_books!
+-- params: user_id, book_ids, due
+UPDATE library SET loaned_to = :user_id WHERE book_id IN (:book_ids)
+        ";
+        let res = parse(text, "param_order");
+        assert_eq!(res.unwrap_err().to_string(), "param `due` listed in `params:` is not found in `loan_books`");
+
+        let text = "
+-- name: loan_books!
+-- params: user_id
+UPDATE library SET loaned_to = :user_id, loaned_on = :loaned_on WHERE book_id IN (:book_ids)
+        ";
+        let res = parse(text, "param_order");
+        assert_eq!(res.unwrap_err().to_string(), "param `loaned_on` is not listed in `params:` of `loan_books`");
+
+        let text = "
+-- name: loan_books!
+-- params: user_id, book_ids, user_id
+UPDATE library SET loaned_to = :user_id WHERE book_id IN (:book_ids)
+        ";
+        let res = parse(text, "param_order");
+        assert_eq!(res.unwrap_err().to_string(), "param `user_id` is listed more than once in `params:` of `loan_books`");
+    }
+
+    #[test]
+    fn parse_multiline_param_docs() {
+        use super::parse;
+
+        let text = "
+-- param: tenant_id: &TenantId - tenant scope
+--   that is set by the session
+
+-- name: loan_books!
+-- Loans books to the user
+-- param: user_id: &str - user ID,
+--   which must be an active patron
+--   of the library
+-- param: book_ids: i64 - book IDs
+--   #[must_use]
+--  @timeout: 30
+-- Books are loaned only if none of them
+--   are already on loan
+UPDATE library SET loaned_to = :user_id WHERE tenant_id = :tenant_id AND book_id IN (:book_ids)
+        ";
+        let sql = parse(text, "multiline_param_docs").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.docs.as_deref(), Some(concat!(
+            " Loans books to the user\n",
+            " * `user_id` - user ID,\n",
+            "   which must be an active patron\n",
+            "   of the library\n",
+            " * `book_ids` - book IDs\n",
+            " * `tenant_id` - tenant scope\n",
+            "   that is set by the session\n",
+            " Books are loaned only if none of them\n",
+            "   are already on loan",
+        )));
+        assert_eq!(stmt.rust_attrs, ["#[must_use]"]);
+        assert_eq!(stmt.attrs.len(), 1);
+        assert_eq!(stmt.attrs[0].name, "timeout");
+        assert_eq!(stmt.attrs[0].value, "30");
+    }
+
+    #[test]
+    fn parse_param_defaults() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+-- param: status: &str = \"active\" - loan status
+-- param: offset: i64 = (PAGE - 1) - first row
+-- param: size: i64 = 100 - size
+-- param: limit: i64 = 100
+-- param: title: &str - title pattern
+SELECT * FROM books WHERE title LIKE :title AND status = :status LIMIT :limit OFFSET :offset * :size
+        ";
+        let sql = parse(text, "param_defaults").unwrap();
+        let stmt = &sql.stmt_list[0];
+        let defaults = stmt.params.iter().map(|param| (param.name.as_str(), param.default.as_deref())).collect::<Vec<_>>();
+        assert_eq!(defaults, [("status", Some("\"active\"")), ("offset", Some("(PAGE - 1)")), ("size", Some("100")), ("limit", Some("100")), ("title", None)]);
+        assert_eq!(stmt.docs.as_deref(), Some(" * `status` - loan status\n * `offset` - first row\n * `size` - size\n * `limit` \n * `title` - title pattern"));
+    }
+
+    #[test]
+    fn parse_param_default_like_docs() {
+        use super::parse;
+
+        let text = "
+-- name: find_books?
+-- param: ratio: f64 = ratio of things
+-- param: limit: i64 = 100 +
+SELECT * FROM books WHERE ratio > :ratio LIMIT :limit
+        ";
+        let sql = parse(text, "param_default_like_docs").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert!(stmt.params.iter().all(|param| param.default.is_none()));
+        assert_eq!(stmt.docs.as_deref(), Some(" * `ratio` = ratio of things\n * `limit` = 100 +"));
+    }
+
+    #[test]
+    fn parse_nullable_params() {
+        use super::parse;
+
+        let text = "
+-- name: update_books!
+-- param: author: Option<&str> - book author
+UPDATE books
+   SET author = :author, editor = :editor?
+ WHERE editor = :editor
+   AND author <> :author
+   AND published >= :year?
+        ";
+        let sql = parse(text, "nullable_params").unwrap();
+        let stmt = &sql.stmt_list[0];
+        assert_eq!(stmt.nullable, ["editor", "year"]);
+        let items = stmt.items.iter().map(|item| match item {
+            StmtItem::Text(text) => text.trim().to_string(),
+            StmtItem::Bind(name) => format!(":{}", name),
+            StmtItem::NullSafe(name, op) => format!("{}{}", op.trim(), name),
+            item => panic!("unexpected {:?}", item)
+        }).collect::<Vec<_>>();
+        assert_eq!(items, [
+            "UPDATE books\n   SET author =", ":author", ", editor =", ":editor",
+            "WHERE editor", "=editor", "AND author", "<>author", "AND published >=", ":year",
+        ]);
+    }
+
+    #[test]
+    fn parse_imports() {
+        use super::parse_text;
+
+        let text = "
+-- Library queries
+-- import: ./common.sql
+-- import: /sql/audit.sql
+-- name: get_book?
+SELECT {{book_columns}} FROM books WHERE book_id = :book_id
+        ";
+        let mut sql_text = parse_text(text).unwrap();
+        assert_eq!(sql_text.imports(), ["./common.sql", "/sql/audit.sql"]);
+
+        let common = parse_text("
+-- fragment: book_columns
+book_id, title
+/
+-- name: count_books?
+SELECT count(*) FROM books
+        ").unwrap();
+        sql_text.import(common);
+        let audit = parse_text("
+-- name: get_audit_log?
+SELECT * FROM audit_log
+        ").unwrap();
+        sql_text.import(audit);
+
+        let sql = sql_text.into_included_sql("library").unwrap();
+        assert_eq!(sql.docs.as_deref(), Some(" Library queries"));
+        let names = sql.stmt_list.iter().map(|stmt| stmt.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["count_books", "get_audit_log", "get_book"]);
+        match &sql.stmt_list[2].items[0] {
+            StmtItem::Text(text) => assert_eq!(text, "SELECT book_id, title FROM books WHERE book_id = "),
+            item => panic!("unexpected {:?}", item)
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`import: ./common.sql` must be placed in the file header")]
+    fn parse_misplaced_import() {
+        use super::parse_text;
+
+        let text = "
+-- name: get_book?
+SELECT * FROM books WHERE book_id = :book_id
+/
+-- import: ./common.sql
+        ";
+        parse_text(text).unwrap();
+    }
+
     #[test]
     fn unique_binds() {
         let text = "